@@ -1,7 +1,9 @@
-use clap::{ArgAction, Parser, ValueHint};
+use clap::{ArgAction, Parser, Subcommand, ValueHint};
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
 use crate::config;
+use crate::wsl::EngineKind;
 
 #[derive(Parser, Debug)]
 #[command(
@@ -11,23 +13,164 @@ use crate::config;
     after_help = config::EXAMPLE_CONFIG
 )]
 pub struct Args {
-    /// Path to YAML configuration file
-    #[arg(short, long, value_hint = ValueHint::FilePath, default_value = "config.yaml")]
-    pub config: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to YAML configuration file; repeat to layer files, later ones override earlier ones
+    #[arg(short, long, global = true, value_hint = ValueHint::FilePath, default_value = "config.yaml")]
+    pub config: Vec<PathBuf>,
 
     /// Show what would be done without creating the instance
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub dry_run: bool,
 
+    /// How to talk to WSL; `fake` simulates it in a JSON state file (see WSLFORGE_FAKE_STATE)
+    #[arg(long, global = true, value_enum, default_value_t)]
+    pub engine: EngineKind,
+
+    /// wsl.exe to run: a bare name looked up on PATH or a path (overrides `wsl_exe` in the config)
+    #[arg(long, global = true, env = "WSLFORGE_WSL_EXE", value_hint = ValueHint::FilePath)]
+    pub wsl_exe: Option<PathBuf>,
+
+    /// Record every wsl.exe/dism.exe/wslpath invocation and its output to a transcript file
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "replay", value_hint = ValueHint::FilePath)]
+    pub record: Option<PathBuf>,
+
+    /// Answer wsl.exe/dism.exe invocations from a recorded transcript instead of running them
+    #[arg(long, global = true, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub replay: Option<PathBuf>,
+
+    /// State file recording the instances wslforge created [default: %LOCALAPPDATA%\wslforge\state.json]
+    #[arg(long, global = true, value_name = "FILE", env = "WSLFORGE_STATE", value_hint = ValueHint::FilePath)]
+    pub state: Option<PathBuf>,
+
     /// Enable extra debug output and artifacts
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub debug: bool,
 
     /// Increase verbosity (-v, -vv)
-    #[arg(short, long, action = ArgAction::Count)]
+    #[arg(short, long, global = true, action = ArgAction::Count)]
     pub verbose: u8,
 
     /// Print a minimal example config to stdout and exit
     #[arg(long)]
     pub print_config: bool,
 }
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SnapshotAction {
+    /// List a profile's snapshots and backups
+    List {
+        /// Profile name as declared under `profiles`
+        profile: String,
+    },
+    /// Delete a snapshot or backup
+    Rm {
+        /// Profile name as declared under `profiles`
+        profile: String,
+
+        /// Snapshot or backup name
+        snapshot: String,
+    },
+}
+
+impl Args {
+    /// The subcommand to run; `apply` when none is given.
+    pub fn command(&self) -> Command {
+        self.command.clone().unwrap_or(Command::Apply {
+            keep_going: false,
+            jobs: NonZeroUsize::MIN,
+            no_rollback: false,
+            backup: false,
+        })
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Create every profile's WSL instance (default)
+    Apply {
+        /// Carry on with the remaining profiles when one fails, and report them all at the end
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Number of profiles to provision at the same time
+        #[arg(short, long, default_value = "1")]
+        jobs: NonZeroUsize,
+
+        /// Leave a failed profile's instance, install dir and user-data file in place for debugging
+        #[arg(long)]
+        no_rollback: bool,
+
+        /// Back up every instance before overriding it, as if all profiles set `backup_before_override`
+        #[arg(long)]
+        backup: bool,
+    },
+    /// Show what `apply` would do without changing anything
+    Plan {
+        /// Print the plan as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+
+        /// Exit with 0 when nothing would change, 1 on error and 2 when changes are pending
+        #[arg(long)]
+        detailed_exitcode: bool,
+    },
+    /// Unregister profiles' WSL instances and remove their install dirs, user-data and state
+    Destroy {
+        /// Profile names as declared under `profiles`
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        profiles: Vec<String>,
+
+        /// Destroy the instances of every profile in the config
+        #[arg(long)]
+        all: bool,
+
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Also destroy instances that wslforge did not create
+        #[arg(long)]
+        force: bool,
+    },
+    /// Export a profile's instance as a named snapshot, or manage its snapshots
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Snapshot {
+        #[command(subcommand)]
+        action: Option<SnapshotAction>,
+
+        /// Profile name as declared under `profiles`
+        #[arg(required = true)]
+        profile: Option<String>,
+
+        /// Snapshot name (default: `snapshot-<UTC timestamp>`)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Re-import a snapshot or backup under the profile's instance name; lists them without one
+    Restore {
+        /// Profile name as declared under `profiles`
+        profile: String,
+
+        /// Snapshot or backup name, as listed when it is omitted
+        snapshot: Option<String>,
+
+        /// Replace the instance if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the profiles declared in the config
+    List,
+    /// Show whether each profile's WSL instance exists
+    Status,
+    /// Render a profile's cloud-init user-data to stdout
+    Render {
+        /// Profile name as declared under `profiles`
+        profile: String,
+    },
+    /// Print the JSON Schema of the config file format
+    Schema,
+    /// Check the config and report every problem, without touching WSL
+    Validate,
+}
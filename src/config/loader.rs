@@ -1,7 +1,37 @@
-use crate::config::{Profile, RootConfig};
+use crate::config::model::default_hostname;
+use crate::config::{merge, paths};
+use crate::config::{Profile, ProfileMerge, RootConfig};
 use anyhow::Context;
+use serde_yaml::{Mapping, Value};
 use std::collections::BTreeMap;
-use std::{fs, path::Path};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Top-level keys that mark a file as the `profiles` format rather than a single profile.
+pub(crate) const ROOT_KEYS: &[&str] = &[
+    "profiles",
+    "defaults",
+    "include",
+    "profile_merge",
+    "wsl_exe",
+    "timeouts",
+];
+
+// One parsed config file, in the order it is merged. `raw` is kept to locate keys; for a
+// single-profile file, `root` is wrapped as `profiles: { <hostname>: ... }` and
+// `single_profile` is set, since its keys sit at the top level of `raw`.
+pub(crate) struct Document {
+    pub(crate) path: PathBuf,
+    pub(crate) raw: String,
+    pub(crate) root: Mapping,
+    pub(crate) single_profile: bool,
+}
+
+// The merged config before deserializing, and which file last defined each profile.
+pub(crate) struct Merged {
+    pub(crate) root: Mapping,
+    pub(crate) origins: BTreeMap<String, PathBuf>,
+}
 
 fn format_yaml_error(path: &Path, err: &serde_yaml::Error) -> String {
     if let Some(loc) = err.location() {
@@ -11,23 +41,273 @@ fn format_yaml_error(path: &Path, err: &serde_yaml::Error) -> String {
     }
 }
 
-pub fn load_yaml(path: &Path) -> anyhow::Result<RootConfig> {
+// Loads and merges one or more config files. Later files (and a file over the files it
+// includes) override earlier ones key by key.
+pub fn load_yaml(paths: &[PathBuf]) -> anyhow::Result<RootConfig> {
+    let mut documents = Vec::new();
+    for path in paths {
+        collect_documents(path, &mut Vec::new(), &mut documents)?;
+    }
+    let merged = merge_documents(&documents)?;
+    deserialize_root(merged.root, &merged.origins)
+}
+
+pub(crate) fn merge_documents(documents: &[Document]) -> anyhow::Result<Merged> {
+    let strategy = profile_merge_strategy(documents)?;
+    let mut root = Mapping::new();
+    let mut origins = BTreeMap::new();
+    for document in documents {
+        merge::merge_document(&mut root, &document.path, document.root.clone(), strategy, &mut origins)?;
+    }
+
+    merge::apply_inheritance(&mut root).context("invalid profile inheritance")?;
+    root.remove("profile_merge");
+    Ok(Merged { root, origins })
+}
+
+// Reads `path` and, depth-first, the files it includes. Included files come before the
+// including file so that the latter wins when merging.
+pub(crate) fn collect_documents(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    documents: &mut Vec<Document>,
+) -> anyhow::Result<()> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("unable to read config file: {}", path.display()))?;
+    if stack.contains(&canonical) {
+        let chain: Vec<String> = stack
+            .iter()
+            .chain([&canonical])
+            .map(|p| p.display().to_string())
+            .collect();
+        anyhow::bail!("config include cycle: {}", chain.join(" -> "));
+    }
+
+    let (raw, mut root, single_profile) = read_document(path)?;
+    let absolute = std::path::absolute(path)?;
+    let base_dir = absolute.parent().unwrap_or_else(|| Path::new(".")).to_path_buf();
+    paths::anchor_relative_paths(&mut root, &base_dir)
+        .with_context(|| format!("{}: unable to resolve relative paths", path.display()))?;
+    let includes = match root.remove("include") {
+        None | Some(Value::Null) => Vec::new(),
+        Some(value) => serde_yaml::from_value::<Vec<String>>(value)
+            .with_context(|| format!("{}: `include` must be a list of paths or globs", path.display()))?,
+    };
+
+    stack.push(canonical);
+    for pattern in &includes {
+        for included in
+            expand_include(&base_dir, pattern).with_context(|| format!("{}: bad include", path.display()))?
+        {
+            collect_documents(&included, stack, documents)?;
+        }
+    }
+    stack.pop();
+
+    documents.push(Document {
+        path: path.to_path_buf(),
+        raw,
+        root,
+        single_profile,
+    });
+    Ok(())
+}
+
+fn read_document(path: &Path) -> anyhow::Result<(String, Mapping, bool)> {
     let raw = fs::read_to_string(path).with_context(|| format!("unable to read config file: {}", path.display()))?;
+    let doc: Value =
+        serde_yaml::from_str(&raw).map_err(|e| anyhow::anyhow!("invalid yaml\n- {}", format_yaml_error(path, &e)))?;
+
+    match doc {
+        Value::Mapping(map) if is_root_format(&map) => Ok((raw, map, false)),
+        Value::Mapping(map) => Ok((raw, wrap_single_profile(map), true)),
+        Value::Null => anyhow::bail!("{}: config file is empty", path.display()),
+        _ => anyhow::bail!(
+            "{}: expected either:\n- profiles:\n    <name>:\n      <profile>\n- or a single profile object at the root",
+            path.display()
+        ),
+    }
+}
+
+fn is_root_format(map: &Mapping) -> bool {
+    ROOT_KEYS.iter().any(|key| map.contains_key(*key))
+}
+
+// Backward-compatible form: the whole file is one profile, keyed by its hostname. The
+// profile itself is checked later, together with the others.
+fn wrap_single_profile(map: Mapping) -> Mapping {
+    let hostname = map
+        .get("hostname")
+        .and_then(Value::as_str)
+        .map_or_else(default_hostname, str::to_string);
+    let mut profiles = Mapping::new();
+    profiles.insert(Value::String(hostname), Value::Mapping(map));
+    let mut root = Mapping::new();
+    root.insert(Value::String("profiles".into()), Value::Mapping(profiles));
+    root
+}
+
+// Include entries are relative to the including file. Globs expand in sorted order; a
+// plain path must exist, while a glob may match nothing.
+fn expand_include(base_dir: &Path, pattern: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let full = base_dir.join(pattern);
+    let full_str = full.to_string_lossy();
+    if !full_str.contains(['*', '?', '[']) {
+        if !full.exists() {
+            anyhow::bail!("included file not found: {}", full.display());
+        }
+        return Ok(vec![full]);
+    }
+
+    let mut matches = glob::glob(&full_str)
+        .with_context(|| format!("invalid include glob: {}", pattern))?
+        .collect::<Result<Vec<_>, _>>()?;
+    matches.sort();
+    Ok(matches)
+}
+
+// The strategy declared by the last file that sets `profile_merge`, consistent with later
+// files overriding earlier ones.
+fn profile_merge_strategy(documents: &[Document]) -> anyhow::Result<ProfileMerge> {
+    let mut strategy = ProfileMerge::default();
+    for document in documents {
+        if let Some(value) = document.root.get("profile_merge") {
+            strategy = serde_yaml::from_value(value.clone())
+                .with_context(|| format!("{}: invalid `profile_merge`", document.path.display()))?;
+        }
+    }
+    Ok(strategy)
+}
+
+// Deserializes each merged profile on its own first, so errors name the offending profile
+// and the file that last defined it (locations are lost once files have been merged).
+fn deserialize_root(root: Mapping, origins: &BTreeMap<String, PathBuf>) -> anyhow::Result<RootConfig> {
+    if let Some(Value::Mapping(profiles)) = root.get("profiles") {
+        for (name, profile) in profiles {
+            let name = name.as_str().unwrap_or("?");
+            serde_yaml::from_value::<Profile>(profile.clone()).with_context(|| match origins.get(name) {
+                Some(origin) => format!("{}: invalid profile '{}'", origin.display(), name),
+                None => format!("invalid profile '{}'", name),
+            })?;
+        }
+    }
+    serde_yaml::from_value(Value::Mapping(root)).context("invalid config")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn later_config_overrides_earlier_one_key_by_key() {
+        let dir = TempDir::new().unwrap();
+        let base = write(
+            &dir,
+            "base.yaml",
+            "profiles:\n  dev:\n    username: team\n    no_proxy: localhost\n",
+        );
+        let personal = write(&dir, "personal.yaml", "profiles:\n  dev:\n    username: me\n");
+
+        let config = load_yaml(&[base, personal]).unwrap();
+        let dev = config.profile("dev").unwrap();
+        assert_eq!(dev.username, "me");
+        assert_eq!(dev.no_proxy.as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn including_file_wins_over_included_files_in_glob_order() {
+        let dir = TempDir::new().unwrap();
+        write(
+            &dir,
+            "conf.d/10-a.yaml",
+            "profiles:\n  dev: { username: a, no_proxy: a }\n",
+        );
+        write(
+            &dir,
+            "conf.d/20-b.yaml",
+            "profiles:\n  dev: { no_proxy: b }\n  extra: {}\n",
+        );
+        let main = write(
+            &dir,
+            "main.yaml",
+            "include: [conf.d/*.yaml]\nprofiles:\n  dev: { hostname: dev }\n",
+        );
+
+        let config = load_yaml(&[main]).unwrap();
+        let dev = config.profile("dev").unwrap();
+        assert_eq!(dev.username, "a");
+        assert_eq!(dev.no_proxy.as_deref(), Some("b"));
+        assert_eq!(dev.hostname, "dev");
+        assert!(config.profiles.contains_key("extra"));
+    }
+
+    #[test]
+    fn duplicate_profile_is_rejected_under_profile_merge_error() {
+        let dir = TempDir::new().unwrap();
+        let base = write(
+            &dir,
+            "base.yaml",
+            "profile_merge: error\nprofiles:\n  dev: { username: team }\n",
+        );
+        let personal = write(&dir, "personal.yaml", "profiles:\n  dev: { username: me }\n");
+
+        let err = load_yaml(&[base.clone(), personal.clone()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "profile 'dev' is defined in both {} and {} (profile_merge: error)",
+                base.display(),
+                personal.display()
+            )
+        );
+    }
+
+    #[test]
+    fn distinct_profiles_are_accepted_under_profile_merge_error() {
+        let dir = TempDir::new().unwrap();
+        let base = write(&dir, "base.yaml", "profile_merge: error\nprofiles:\n  dev: {}\n");
+        let personal = write(&dir, "personal.yaml", "profiles:\n  test: {}\n");
+
+        let config = load_yaml(&[base, personal]).unwrap();
+        assert_eq!(config.profiles.len(), 2);
+    }
+
+    #[test]
+    fn include_cycle_is_reported() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "a.yaml", "include: [b.yaml]\nprofiles:\n  a: {}\n");
+        write(&dir, "b.yaml", "include: [a.yaml]\nprofiles:\n  b: {}\n");
+
+        let err = load_yaml(&[dir.path().join("a.yaml")]).unwrap_err();
+        assert!(err.to_string().starts_with("config include cycle: "), "{err}");
+    }
+
+    #[test]
+    fn missing_plain_include_is_an_error_but_empty_glob_is_not() {
+        let dir = TempDir::new().unwrap();
+        let missing = write(&dir, "missing.yaml", "include: [nope.yaml]\nprofiles:\n  dev: {}\n");
+        let glob = write(&dir, "glob.yaml", "include: [nope/*.yaml]\nprofiles:\n  dev: {}\n");
+
+        let err = load_yaml(&[missing]).unwrap_err();
+        assert!(format!("{err:#}").contains("included file not found"), "{err:#}");
+        assert!(load_yaml(&[glob]).is_ok());
+    }
+
+    #[test]
+    fn single_profile_file_is_keyed_by_its_hostname() {
+        let dir = TempDir::new().unwrap();
+        let path = write(&dir, "single.yaml", "hostname: solo\nusername: me\n");
 
-    match serde_yaml::from_str::<RootConfig>(&raw) {
-        Ok(cfg) => Ok(cfg),
-        Err(root_err) => match serde_yaml::from_str::<Profile>(&raw) {
-            Ok(profile) => {
-                let mut profiles = BTreeMap::new();
-                let name = profile.hostname.clone();
-                profiles.insert(name, profile);
-                Ok(RootConfig { profiles })
-            }
-            Err(profile_err) => Err(anyhow::anyhow!(
-                "invalid yaml\n- profiles format error: {}\n- single-profile format error: {}\n\nExpected either:\n- profiles:\n    <name>:\n      <profile>\n- or a single profile object at the root",
-                format_yaml_error(path, &root_err),
-                format_yaml_error(path, &profile_err)
-            )),
-        },
+        let config = load_yaml(&[path]).unwrap();
+        assert_eq!(config.profile("solo").unwrap().username, "me");
     }
 }
@@ -0,0 +1,168 @@
+// Finds where a key path (e.g. `profiles` / `dev` / `image` / `path`) is written in a YAML
+// source. `serde_yaml::Value` keeps no spans, so this scans the text by indentation. It
+// understands block mappings and, for the last step, flow mappings on a single line
+// (`image: { type: file, path: x }`), which covers how wslforge configs are written.
+
+/// A 1-based line and column in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+pub(crate) fn locate(raw: &str, keys: &[&str]) -> Option<Location> {
+    let lines: Vec<&str> = raw.lines().collect();
+    let mut block = 0..lines.len();
+    let mut parent: Option<(usize, usize)> = None;
+    let mut found = None;
+
+    for key in keys {
+        match find_block_key(&lines, block.clone(), key) {
+            Some((index, indent)) => {
+                found = Some(Location {
+                    line: index + 1,
+                    column: indent + 1,
+                });
+                parent = Some((index, indent));
+                block = index + 1..block_end(&lines, index + 1, block.end, indent);
+            }
+            None => {
+                let (index, _) = parent?;
+                let column = find_flow_key(lines[index], key)?;
+                return Some(Location {
+                    line: index + 1,
+                    column: column + 1,
+                });
+            }
+        }
+    }
+    found
+}
+
+// Looks for `key:` among the direct children of a block, i.e. at the indentation of the
+// block's first meaningful line.
+fn find_block_key(lines: &[&str], block: std::ops::Range<usize>, key: &str) -> Option<(usize, usize)> {
+    let mut child_indent = None;
+    for index in block {
+        let Some(indent) = meaningful_indent(lines[index]) else {
+            continue;
+        };
+        let child_indent = *child_indent.get_or_insert(indent);
+        if indent != child_indent {
+            continue;
+        }
+        if key_at(&lines[index][indent..], key) {
+            return Some((index, indent));
+        }
+    }
+    None
+}
+
+fn block_end(lines: &[&str], start: usize, limit: usize, indent: usize) -> usize {
+    (start..limit)
+        .find(|&index| meaningful_indent(lines[index]).is_some_and(|i| i <= indent))
+        .unwrap_or(limit)
+}
+
+fn find_flow_key(line: &str, key: &str) -> Option<usize> {
+    let open = line.find('{')?;
+    let mut offset = open + 1;
+    for part in line[open + 1..].split(',') {
+        let trimmed = part.trim_start();
+        let start = offset + (part.len() - trimmed.len());
+        if key_at(trimmed, key) {
+            return Some(start);
+        }
+        offset += part.len() + 1;
+    }
+    None
+}
+
+fn meaningful_indent(line: &str) -> Option<usize> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("---") {
+        return None;
+    }
+    Some(line.len() - trimmed.len())
+}
+
+fn key_at(text: &str, key: &str) -> bool {
+    [key.to_string(), format!("\"{key}\""), format!("'{key}'")]
+        .iter()
+        .any(|candidate| {
+            text.strip_prefix(candidate.as_str())
+                .is_some_and(|rest| rest.trim_start().starts_with(':'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# team config
+include:
+  - base.yaml
+  - conf.d/*.yaml
+profiles:
+  dev:
+    hostname: dev
+    image: { type: file, path: rootfs.tar }
+    cloud_init:
+      type: inline
+      content: |
+        #cloud-config
+        hostname: not-a-key
+    tags:
+    - name: first
+    - hostname: also-not-a-key
+    "username": quoted
+  test:
+    # comment between keys
+    hostname: test
+"#;
+
+    fn at(line: usize, column: usize) -> Option<Location> {
+        Some(Location { line, column })
+    }
+
+    #[test]
+    fn finds_top_level_and_nested_block_keys() {
+        assert_eq!(locate(CONFIG, &["include"]), at(2, 1));
+        assert_eq!(locate(CONFIG, &["profiles"]), at(5, 1));
+        assert_eq!(locate(CONFIG, &["profiles", "dev"]), at(6, 3));
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "hostname"]), at(7, 5));
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "cloud_init", "type"]), at(10, 7));
+        assert_eq!(locate(CONFIG, &["profiles", "test", "hostname"]), at(20, 5));
+    }
+
+    #[test]
+    fn finds_keys_in_a_flow_mapping() {
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "image"]), at(8, 5));
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "image", "type"]), at(8, 14));
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "image", "path"]), at(8, 26));
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "image", "name"]), None);
+    }
+
+    #[test]
+    fn skips_sequence_items_and_block_scalars() {
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "tags"]), at(14, 5));
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "username"]), at(17, 5));
+        // Keys inside `content: |` or a sequence item are not the profile's own keys.
+        assert_eq!(locate(CONFIG, &["profiles", "dev", "cloud_init", "hostname"]), None);
+        assert_eq!(locate("list:\n- hostname: x\nother: 1\n", &["hostname"]), None);
+        assert_eq!(locate("list:\n- a\n- b\nother: 1\n", &["other"]), at(4, 1));
+    }
+
+    #[test]
+    fn does_not_leak_into_sibling_blocks() {
+        assert_eq!(locate(CONFIG, &["profiles", "test", "image"]), None);
+        assert_eq!(locate(CONFIG, &["profiles", "missing"]), None);
+        assert_eq!(locate(CONFIG, &["hostname"]), None);
+    }
+
+    #[test]
+    fn handles_crlf_and_document_markers() {
+        let raw = "---\r\nprofiles:\r\n  dev:\r\n    username: me\r\n";
+        assert_eq!(locate(raw, &["profiles", "dev", "username"]), at(4, 5));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::config::ProfileMerge;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// Deep-merges `overlay` into `base`: mappings are merged key by key, everything else
+// (scalars, sequences, null) is replaced. Two mappings carrying different `type` tags
+// (e.g. `image: { type: distro }` vs `image: { type: file }`) describe different enum
+// variants, so the overlay replaces the base wholesale instead of mixing their fields.
+pub(crate) fn deep_merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base_map), Value::Mapping(overlay_map)) if !tags_differ(base_map, &overlay_map) => {
+            merge_mapping(base_map, overlay_map)
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn merge_mapping(base: &mut Mapping, overlay: Mapping) {
+    for (key, value) in overlay {
+        match base.get_mut(&key) {
+            Some(existing) => deep_merge(existing, value),
+            None => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+// Merges one config file into the accumulated root. Top-level keys are deep-merged; a
+// profile already defined by an earlier file is merged or rejected according to `strategy`.
+// `origins` remembers which file last defined each profile, for error messages.
+pub(crate) fn merge_document(
+    root: &mut Mapping,
+    path: &Path,
+    mut document: Mapping,
+    strategy: ProfileMerge,
+    origins: &mut BTreeMap<String, PathBuf>,
+) -> anyhow::Result<()> {
+    if let Some(profiles) = document.remove("profiles") {
+        let Value::Mapping(profiles) = profiles else {
+            anyhow::bail!(
+                "{}: `profiles` must be a mapping of profile name to profile",
+                path.display()
+            );
+        };
+        let merged = root
+            .entry(Value::String("profiles".into()))
+            .or_insert_with(|| Value::Mapping(Mapping::new()));
+        let Value::Mapping(merged) = merged else {
+            unreachable!("`profiles` is always inserted as a mapping");
+        };
+
+        for (name, profile) in profiles {
+            let name_str = profile_name(&name)?.to_string();
+            if let Some(existing) = merged.get_mut(&name) {
+                if strategy == ProfileMerge::Error {
+                    anyhow::bail!(
+                        "profile '{}' is defined in both {} and {} (profile_merge: error)",
+                        name_str,
+                        origins
+                            .get(&name_str)
+                            .map(|p| p.display().to_string())
+                            .unwrap_or_default(),
+                        path.display()
+                    );
+                }
+                deep_merge(existing, profile);
+            } else {
+                merged.insert(name, profile);
+            }
+            origins.insert(name_str, path.to_path_buf());
+        }
+    }
+
+    merge_mapping(root, document);
+    Ok(())
+}
+
+fn tags_differ(base: &Mapping, overlay: &Mapping) -> bool {
+    match (base.get("type"), overlay.get("type")) {
+        (Some(a), Some(b)) => a != b,
+        _ => false,
+    }
+}
+
+// Resolves `defaults` and per-profile `extends` into standalone profile mappings.
+// Each profile becomes `defaults <- ancestors... <- profile`; the `defaults` key is
+// removed from the root afterwards.
+pub(crate) fn apply_inheritance(root: &mut Mapping) -> anyhow::Result<()> {
+    let defaults = match root.remove("defaults") {
+        None | Some(Value::Null) => Value::Mapping(Mapping::new()),
+        Some(value @ Value::Mapping(_)) => value,
+        Some(_) => anyhow::bail!("`defaults` must be a mapping of profile fields"),
+    };
+    if defaults.get("extends").is_some() {
+        anyhow::bail!("`defaults` cannot use `extends`");
+    }
+
+    let Some(profiles) = root.get("profiles") else {
+        return Ok(());
+    };
+    let Value::Mapping(raw_profiles) = profiles else {
+        anyhow::bail!("`profiles` must be a mapping of profile name to profile");
+    };
+
+    let mut resolved = Mapping::new();
+    for name in raw_profiles.keys() {
+        let name = profile_name(name)?;
+        let mut chain = Vec::new();
+        let profile = resolve_profile(name, raw_profiles, &defaults, &mut chain)?;
+        resolved.insert(Value::String(name.to_string()), profile);
+    }
+    root.insert(Value::String("profiles".into()), Value::Mapping(resolved));
+    Ok(())
+}
+
+fn resolve_profile(
+    name: &str,
+    raw_profiles: &Mapping,
+    defaults: &Value,
+    chain: &mut Vec<String>,
+) -> anyhow::Result<Value> {
+    if chain.iter().any(|seen| seen == name) {
+        chain.push(name.to_string());
+        anyhow::bail!("profile inheritance cycle: {}", chain.join(" -> "));
+    }
+    chain.push(name.to_string());
+
+    let profile = match raw_profiles.get(name) {
+        Some(Value::Null) => Value::Mapping(Mapping::new()),
+        Some(value @ Value::Mapping(_)) => value.clone(),
+        Some(_) => anyhow::bail!("profile '{}' must be a mapping", name),
+        None => anyhow::bail!("profile '{}' not found", name),
+    };
+
+    let mut merged = match profile.get("extends") {
+        None | Some(Value::Null) => defaults.clone(),
+        Some(Value::String(parent)) => {
+            if !raw_profiles.contains_key(parent.as_str()) {
+                anyhow::bail!("profile '{}' extends unknown profile '{}'", name, parent);
+            }
+            resolve_profile(parent, raw_profiles, defaults, chain)?
+        }
+        Some(_) => anyhow::bail!("profile '{}': `extends` must be a profile name", name),
+    };
+    deep_merge(&mut merged, profile);
+    Ok(merged)
+}
+
+fn profile_name(key: &Value) -> anyhow::Result<&str> {
+    key.as_str()
+        .ok_or_else(|| anyhow::anyhow!("profile names must be strings, got {:?}", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(source: &str) -> Mapping {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    fn resolved(source: &str) -> anyhow::Result<Mapping> {
+        let mut root = yaml(source);
+        apply_inheritance(&mut root)?;
+        Ok(root)
+    }
+
+    fn field<'a>(root: &'a Mapping, profile: &str, path: &[&str]) -> &'a Value {
+        let mut value = &root["profiles"][profile];
+        for key in path {
+            value = &value[*key];
+        }
+        value
+    }
+
+    #[test]
+    fn profile_wins_over_parent_and_parent_over_defaults() {
+        let root = resolved(
+            r#"
+defaults:
+  username: fromdefaults
+  install_dir: C:/defaults
+  http_proxy: http://defaults:3128
+profiles:
+  base:
+    username: frombase
+    install_dir: C:/base
+  child:
+    extends: base
+    install_dir: C:/child
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(field(&root, "child", &["install_dir"]), "C:/child");
+        assert_eq!(field(&root, "child", &["username"]), "frombase");
+        assert_eq!(field(&root, "child", &["http_proxy"]), "http://defaults:3128");
+        assert_eq!(field(&root, "base", &["install_dir"]), "C:/base");
+        assert!(!root.contains_key("defaults"));
+    }
+
+    #[test]
+    fn nested_mappings_merge_key_by_key() {
+        let root = resolved(
+            r#"
+defaults:
+  timeouts: { import: 10m, export: 20m }
+profiles:
+  dev:
+    timeouts: { import: 1m }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(field(&root, "dev", &["timeouts", "import"]), "1m");
+        assert_eq!(field(&root, "dev", &["timeouts", "export"]), "20m");
+    }
+
+    #[test]
+    fn different_type_tags_replace_instead_of_merging() {
+        let root = resolved(
+            r#"
+defaults:
+  image: { type: distro, name: Debian }
+profiles:
+  dev:
+    image: { type: file, path: C:/rootfs.tar }
+  plain:
+    image: { type: distro }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(field(&root, "dev", &["image", "path"]), "C:/rootfs.tar");
+        assert!(field(&root, "dev", &["image"]).get("name").is_none());
+        assert_eq!(field(&root, "plain", &["image", "name"]), "Debian");
+    }
+
+    #[test]
+    fn sequences_are_replaced_not_appended() {
+        let mut base = Value::Mapping(yaml("list: [a, b]"));
+        deep_merge(&mut base, Value::Mapping(yaml("list: [c]")));
+        assert_eq!(base["list"], Value::Sequence(vec!["c".into()]));
+    }
+
+    #[test]
+    fn extends_cycle_is_reported_with_its_chain() {
+        let err = resolved(
+            r#"
+profiles:
+  a: { extends: b }
+  b: { extends: c }
+  c: { extends: a }
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "profile inheritance cycle: a -> b -> c -> a");
+    }
+
+    #[test]
+    fn self_extends_is_a_cycle() {
+        let err = resolved("profiles:\n  a: { extends: a }\n").unwrap_err();
+        assert_eq!(err.to_string(), "profile inheritance cycle: a -> a");
+    }
+
+    #[test]
+    fn extends_of_unknown_profile_is_rejected() {
+        let err = resolved("profiles:\n  a: { extends: missing }\n").unwrap_err();
+        assert_eq!(err.to_string(), "profile 'a' extends unknown profile 'missing'");
+    }
+}
@@ -1,8 +1,18 @@
 mod loader;
+mod locate;
+mod merge;
 mod model;
+mod paths;
+mod schema;
+mod secret;
+mod validate;
 
 pub use loader::load_yaml;
-pub use model::{CloudInitSource, ImageSource, Profile, RootConfig};
+pub use locate::Location;
+pub use model::{CloudInitSource, ImageSource, OverridePolicy, Profile, ProfileMerge, RootConfig, Timeouts};
+pub use schema::json_schema;
+pub use secret::PasswordSource;
+pub use validate::{validate, Diagnostic, Severity};
 
 pub const EXAMPLE_CONFIG: &str = r#"─── Example Config ───────────────────────────────────────────────
 
@@ -25,4 +35,5 @@ profiles:
 ─── Tips ─────────────────────────────────────────────────────────
 • Use `--print-config` to print this example.
 • Use `--config` to point to your YAML file.
+• Use `plan` to preview and `apply` to create instances.
 "#;
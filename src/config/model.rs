@@ -3,14 +3,19 @@
 // - Nothing writes this config back to disk.
 // - `skip_serializing_if` on `Option<T>` makes `None` act like "missing" in templates, so
 //   `| default('...')` works as expected.
-// - `password` is optional; we hash it when rendering cloud-init templates.
+// - `password` is optional and never serialized; it is read from its source and hashed
+//   when rendering cloud-init templates.
+// - `JsonSchema` backs `wslforge schema`; the `///` docs become editor hover text.
+use crate::config::PasswordSource;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fmt;
 use std::path::PathBuf;
+use std::time::Duration;
 use url::Url;
 
-fn default_hostname() -> String {
+pub(crate) fn default_hostname() -> String {
     "UbuntuWSL".into()
 }
 
@@ -22,38 +27,97 @@ fn default_install_dir() -> PathBuf {
     "%userprofile%/VMs".into()
 }
 
-fn default_cloud_init_path() -> PathBuf {
+pub(crate) fn default_cloud_init_path() -> PathBuf {
     "cloud-init.yaml".into()
 }
 
+fn default_backup_retention() -> usize {
+    3
+}
+
 fn default_distro() -> String {
     "Ubuntu".into()
 }
 
-fn is_false(value: &bool) -> bool {
-    !*value
+/// What `apply` does with an instance that already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OverridePolicy {
+    /// Leave it alone.
+    #[default]
+    Never,
+    /// Replace it on every run.
+    Always,
+    /// Replace it when what it is created from changed since wslforge created it:
+    /// `hostname`, `username`, the proxy settings, `install_dir`, `cloud_init`, `image`, the
+    /// rendered cloud-init and the image file's size and modification time. A `password`
+    /// counts by its declaration (`env` name, `file` path, `command` or `hash`), never by
+    /// the value it reads. `override`, backups and `timeouts` do not count.
+    IfChanged,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl OverridePolicy {
+    pub fn is_never(&self) -> bool {
+        *self == OverridePolicy::Never
+    }
+}
+
+impl fmt::Display for OverridePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverridePolicy::Never => "never",
+            OverridePolicy::Always => "always",
+            OverridePolicy::IfChanged => "if_changed",
+        })
+    }
+}
+
+/// `never`, `always` or `if_changed`; `true` and `false` still mean `always` and `never`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged, expecting = "one of `never`, `always`, `if_changed`, or a boolean")]
+enum OverrideSetting {
+    Flag(bool),
+    Policy(OverridePolicy),
+}
+
+fn deserialize_override<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<OverridePolicy, D::Error> {
+    Ok(match OverrideSetting::deserialize(deserializer)? {
+        OverrideSetting::Flag(true) => OverridePolicy::Always,
+        OverrideSetting::Flag(false) => OverridePolicy::Never,
+        OverrideSetting::Policy(policy) => policy,
+    })
+}
+
+/// Where the instance's root filesystem comes from.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum ImageSource {
+    /// Install an official WSL distro (`wsl --install`).
     Distro {
+        /// Distro name as listed by `wsl --list --online`.
         #[serde(default = "default_distro")]
         name: String,
     },
+    /// Import a local rootfs archive (`wsl --import`).
     File {
+        /// Path to a `.tar`, `.tar.gz` or `.tgz` rootfs archive.
         path: PathBuf,
     },
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Cloud-init user-data for the instance's first boot.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
 pub enum CloudInitSource {
+    /// Load user-data from a file.
     File {
+        /// Path to the user-data template.
         #[serde(default = "default_cloud_init_path")]
         path: PathBuf,
     },
+    /// Inline user-data.
     Inline {
+        /// The user-data template itself.
         content: String,
     },
 }
@@ -73,36 +137,137 @@ impl Default for ImageSource {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// How long a wsl.exe operation may run before it is killed, e.g. `30s`, `10m` or `1h`.
+/// Unset entries fall back to the top-level `timeouts`, then to built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Timeouts {
+    /// Operations without an entry of their own, e.g. `--list` or `--unregister` (default `5m`).
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub default: Option<Duration>,
+    /// `wsl --install` of a distro, including its download (default `1h`).
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub install: Option<Duration>,
+    /// `wsl --import` of a rootfs archive (default `30m`).
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub import: Option<Duration>,
+    /// `wsl --update` (default `15m`).
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub update: Option<Duration>,
+    /// `wsl --export` of an instance (default `1h`).
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub export: Option<Duration>,
+    /// A command run inside an instance (default: no limit).
+    #[serde(default, with = "humantime_serde", skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub command: Option<Duration>,
+}
+
+impl Timeouts {
+    /// These timeouts, with every entry set in `overrides` replaced.
+    pub fn overridden_by(&self, overrides: &Timeouts) -> Timeouts {
+        Timeouts {
+            default: overrides.default.or(self.default),
+            install: overrides.install.or(self.install),
+            import: overrides.import.or(self.import),
+            update: overrides.update.or(self.update),
+            export: overrides.export.or(self.export),
+            command: overrides.command.or(self.command),
+        }
+    }
+}
+
+/// One WSL instance.
+#[derive(Debug, Clone, Deserialize, Serialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Profile {
-    #[serde(default, skip_serializing_if = "is_false", rename = "override")]
-    pub override_instance: bool,
+    /// Profile to inherit fields from. Resolved by the loader before deserializing; kept
+    /// here so the key is accepted.
+    #[serde(default, skip_serializing)]
+    pub extends: Option<String>,
+    /// What to do when the instance already exists.
+    #[serde(
+        default,
+        skip_serializing_if = "OverridePolicy::is_never",
+        rename = "override",
+        deserialize_with = "deserialize_override"
+    )]
+    #[schemars(with = "OverrideSetting")]
+    pub override_instance: OverridePolicy,
+    /// Export the instance to `<install_dir>/<hostname>.snapshots/` before `override`
+    /// replaces it.
+    #[serde(default, skip_serializing)]
+    pub backup_before_override: bool,
+    /// How many backups to keep per instance; older ones are deleted.
+    #[serde(default = "default_backup_retention", skip_serializing)]
+    pub backup_retention: usize,
+    /// WSL instance name, also used as the cloud-init hostname.
     #[serde(default = "default_hostname")]
     pub hostname: String,
+    /// Default user.
     #[serde(default = "default_username")]
     pub username: String,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub password: Option<String>,
+    /// Password for `username`, hashed into cloud-init as `password_hash`.
+    #[serde(default, skip_serializing)]
+    pub password: Option<PasswordSource>,
 
+    /// HTTP proxy URL.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub http_proxy: Option<Url>,
+    /// HTTPS proxy URL.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub https_proxy: Option<Url>,
+    /// Comma-separated proxy bypass list.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub no_proxy: Option<String>,
 
+    /// Directory under which `<hostname>/` holds an imported instance.
     #[serde(default = "default_install_dir")]
     pub install_dir: PathBuf,
+    /// Cloud-init user-data source.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub cloud_init: Option<CloudInitSource>,
 
+    /// Root filesystem source.
     #[serde(default)]
     pub image: ImageSource,
+
+    /// Timeouts for this profile's operations, over the top-level `timeouts`.
+    #[serde(default, skip_serializing)]
+    pub timeouts: Option<Timeouts>,
+}
+
+/// How a profile defined in more than one config file is combined.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileMerge {
+    /// Deep-merge the definitions, later files winning.
+    #[default]
+    Merge,
+    /// Reject the config.
+    Error,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct RootConfig {
     pub profiles: BTreeMap<String, Profile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wsl_exe: Option<PathBuf>,
+    #[serde(default)]
+    pub timeouts: Timeouts,
+}
+
+impl RootConfig {
+    pub fn profile(&self, name: &str) -> anyhow::Result<&Profile> {
+        self.profiles.get(name).ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            anyhow::anyhow!("profile '{}' not found (available: {})", name, available.join(", "))
+        })
+    }
 }
@@ -0,0 +1,185 @@
+use crate::config::model::default_cloud_init_path;
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+// Rewrites the relative paths of one config file (`install_dir`, `cloud_init.path`,
+// `image.path` and `password.file`, in `defaults` and in every profile, and `wsl_exe` unless
+// it is a bare name to look up on PATH) to absolute paths anchored at `base_dir`, the
+// directory of that file. This runs per file, before files are merged, so a path always
+// resolves against the file that declared it rather than the cwd.
+pub(crate) fn anchor_relative_paths(root: &mut Mapping, base_dir: &Path) -> anyhow::Result<()> {
+    if let Some(value) = root.get_mut("wsl_exe") {
+        if value.as_str().is_some_and(|raw| raw.contains(['/', '\\'])) {
+            anchor_value(value, base_dir)?;
+        }
+    }
+    if let Some(Value::Mapping(defaults)) = root.get_mut("defaults") {
+        anchor_profile(defaults, base_dir)?;
+    }
+    if let Some(Value::Mapping(profiles)) = root.get_mut("profiles") {
+        for (_, profile) in profiles.iter_mut() {
+            if let Value::Mapping(profile) = profile {
+                anchor_profile(profile, base_dir)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn anchor_profile(profile: &mut Mapping, base_dir: &Path) -> anyhow::Result<()> {
+    if let Some(value) = profile.get_mut("install_dir") {
+        anchor_value(value, base_dir)?;
+    }
+    if let Some(Value::Mapping(cloud_init)) = profile.get_mut("cloud_init") {
+        // `type: file` without a `path` means the default file next to this config.
+        if cloud_init.get("type").and_then(Value::as_str) == Some("file") && !cloud_init.contains_key("path") {
+            let default_path = default_cloud_init_path().to_string_lossy().into_owned();
+            cloud_init.insert(Value::String("path".into()), Value::String(default_path));
+        }
+        if let Some(value) = cloud_init.get_mut("path") {
+            anchor_value(value, base_dir)?;
+        }
+    }
+    if let Some(Value::Mapping(image)) = profile.get_mut("image") {
+        if let Some(value) = image.get_mut("path") {
+            anchor_value(value, base_dir)?;
+        }
+    }
+    if let Some(Value::Mapping(password)) = profile.get_mut("password") {
+        if let Some(value) = password.get_mut("file") {
+            anchor_value(value, base_dir)?;
+        }
+    }
+    Ok(())
+}
+
+fn anchor_value(value: &mut Value, base_dir: &Path) -> anyhow::Result<()> {
+    let Value::String(raw) = value else {
+        return Ok(());
+    };
+    if !is_anchorable(raw) {
+        return Ok(());
+    }
+    let anchored = std::path::absolute(base_dir.join(raw.as_str()))?;
+    *raw = anchored.to_string_lossy().into_owned();
+    Ok(())
+}
+
+// Paths starting with an env var (`%VAR%`, `$VAR`) or `~` are expanded later and are left
+// alone, as are absolute paths in either Windows (`C:\`, `\\server`) or POSIX form.
+fn is_anchorable(raw: &str) -> bool {
+    if raw.is_empty() || raw.starts_with(['%', '$', '~', '/', '\\']) {
+        return false;
+    }
+    let bytes = raw.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    !has_drive && Path::new(raw).is_relative()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{load_yaml, CloudInitSource, ImageSource};
+    use std::fs;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn anchored(source: &str, base_dir: &Path) -> Mapping {
+        let mut root: Mapping = serde_yaml::from_str(source).unwrap();
+        anchor_relative_paths(&mut root, base_dir).unwrap();
+        root
+    }
+
+    #[test]
+    fn relative_paths_are_anchored_at_the_base_dir() {
+        let base = std::path::absolute("team").unwrap();
+        let root = anchored(
+            r#"
+wsl_exe: bin/wsl.exe
+defaults:
+  install_dir: vms
+profiles:
+  dev:
+    cloud_init: { type: file, path: init/user-data.yaml }
+    image: { type: file, path: images/rootfs.tar }
+    password: { file: secrets/dev.txt }
+"#,
+            &base,
+        );
+        let dev = &root["profiles"]["dev"];
+        let expect = |rel: &str| Value::String(base.join(rel).to_string_lossy().into_owned());
+
+        assert_eq!(root["wsl_exe"], expect("bin/wsl.exe"));
+        assert_eq!(root["defaults"]["install_dir"], expect("vms"));
+        assert_eq!(dev["cloud_init"]["path"], expect("init/user-data.yaml"));
+        assert_eq!(dev["image"]["path"], expect("images/rootfs.tar"));
+        assert_eq!(dev["password"]["file"], expect("secrets/dev.txt"));
+    }
+
+    #[test]
+    fn default_cloud_init_file_sits_next_to_the_config() {
+        let base = std::path::absolute("team").unwrap();
+        let root = anchored("profiles:\n  dev:\n    cloud_init: { type: file }\n", &base);
+        assert_eq!(
+            root["profiles"]["dev"]["cloud_init"]["path"],
+            Value::String(base.join("cloud-init.yaml").to_string_lossy().into_owned())
+        );
+    }
+
+    #[test]
+    fn absolute_expandable_and_bare_paths_are_left_alone() {
+        for raw in [
+            "C:\\VMs",
+            "c:/VMs",
+            "\\\\server\\share",
+            "/opt/vms",
+            "%USERPROFILE%\\VMs",
+            "$HOME/vms",
+            "~/vms",
+            "",
+        ] {
+            assert!(!is_anchorable(raw), "{raw:?} should not be anchored");
+        }
+        for raw in ["vms", "./vms", "..\\vms", "sub/dir"] {
+            assert!(is_anchorable(raw), "{raw:?} should be anchored");
+        }
+
+        let root = anchored("wsl_exe: wsl.exe\nprofiles: {}\n", Path::new("/team"));
+        assert_eq!(root["wsl_exe"], "wsl.exe");
+    }
+
+    #[test]
+    fn paths_resolve_against_the_declaring_file_not_the_cwd() {
+        let dir = TempDir::new().unwrap();
+        let shared = dir.path().join("shared");
+        let personal = dir.path().join("home/me");
+        fs::create_dir_all(&shared).unwrap();
+        fs::create_dir_all(&personal).unwrap();
+        fs::write(
+            shared.join("base.yaml"),
+            "profiles:\n  dev:\n    install_dir: vms\n    image: { type: file, path: rootfs.tar }\n",
+        )
+        .unwrap();
+        fs::write(
+            personal.join("config.yaml"),
+            "include: [../../shared/base.yaml]\nprofiles:\n  dev:\n    cloud_init: { type: file, path: user-data.yaml }\n",
+        )
+        .unwrap();
+
+        fs::create_dir(shared.join("vms")).unwrap();
+        fs::write(shared.join("rootfs.tar"), "").unwrap();
+        fs::write(personal.join("user-data.yaml"), "").unwrap();
+
+        let config = load_yaml(&[personal.join("config.yaml")]).unwrap();
+        let dev = config.profile("dev").unwrap();
+        // The include goes through `..`, which is kept as written; compare real locations.
+        let same =
+            |actual: &Path, expected: PathBuf| actual.canonicalize().unwrap() == expected.canonicalize().unwrap();
+
+        assert!(same(&dev.install_dir, shared.join("vms")));
+        assert!(matches!(&dev.image, ImageSource::File { path } if same(path, shared.join("rootfs.tar"))));
+        assert!(
+            matches!(&dev.cloud_init, Some(CloudInitSource::File { path }) if same(path, personal.join("user-data.yaml")))
+        );
+    }
+}
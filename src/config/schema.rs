@@ -0,0 +1,37 @@
+use crate::config::{Profile, ProfileMerge, Timeouts};
+use schemars::JsonSchema;
+use std::collections::BTreeMap;
+
+/// A wslforge config file.
+// Describes the file as written, which is more than `RootConfig`: `include`, `defaults`
+// and `profile_merge` are consumed by the loader and never reach `RootConfig`. Only used
+// to generate the schema, never deserialized.
+#[derive(JsonSchema)]
+#[schemars(title = "wslforge config", deny_unknown_fields)]
+#[allow(dead_code)]
+struct ConfigFile {
+    /// Other config files to merge first, relative to this file. Globs are allowed.
+    #[schemars(default)]
+    include: Vec<String>,
+    /// How a profile defined in more than one file is combined.
+    #[schemars(default)]
+    profile_merge: ProfileMerge,
+    /// Fields applied to every profile before its own.
+    #[schemars(default)]
+    defaults: Option<Profile>,
+    /// wsl.exe to run, as a bare name looked up on PATH or a path; `--wsl-exe` and
+    /// `WSLFORGE_WSL_EXE` take precedence.
+    wsl_exe: Option<String>,
+    /// Timeouts for wsl.exe operations; profiles can override each entry.
+    #[schemars(default)]
+    timeouts: Timeouts,
+    /// WSL instances, keyed by profile name.
+    #[schemars(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// The JSON Schema of the config file format, for editors and CI.
+pub fn json_schema() -> anyhow::Result<String> {
+    let schema = schemars::schema_for!(ConfigFile);
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
@@ -0,0 +1,224 @@
+// Where a profile's `password` comes from. Sources are only read when the value is
+// actually needed (rendering cloud-init); whatever is read is registered with `redact`,
+// and `Debug` never prints the secret itself.
+// There is deliberately no `Serialize`: a password never leaves the process through
+// templates, JSON output or logs, only as a hash in the rendered cloud-init.
+use crate::redact;
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// A plaintext password, or where to read it from.
+#[derive(Clone, Deserialize, JsonSchema)]
+#[serde(
+    untagged,
+    deny_unknown_fields,
+    expecting = "a plaintext password or one of { env: VAR }, { file: path }, { command: [argv...] }, { hash: \"$6$...\" }"
+)]
+pub enum PasswordSource {
+    /// Plaintext password.
+    Plain(String),
+    /// Read from an environment variable.
+    Env { env: String },
+    /// Read from a file; a trailing newline is trimmed.
+    File { file: PathBuf },
+    /// Read from a command's stdout, given as argv.
+    Command { command: Vec<String> },
+    /// A pre-computed crypt(3) hash, e.g. `$6$...`.
+    Hash { hash: String },
+}
+
+impl PasswordSource {
+    /// Reads the plaintext password. Not available for the pre-hashed form.
+    pub fn reveal(&self) -> anyhow::Result<String> {
+        let password = self.read()?;
+        redact::register_secret(&password);
+        Ok(password)
+    }
+
+    /// The pre-computed crypt(3) hash, when the password was given as `{ hash: ... }`.
+    pub fn hash(&self) -> Option<&str> {
+        match self {
+            PasswordSource::Hash { hash } => {
+                redact::register_secret(hash);
+                Some(hash)
+            }
+            _ => None,
+        }
+    }
+
+    /// How the password is declared, without reading it: the `env` name, `file` path,
+    /// `command` or `hash`, and a digest for a plaintext one. Fingerprints use this, so a
+    /// command that returns a fresh value each time does not count as a change.
+    pub fn declaration(&self) -> String {
+        match self {
+            PasswordSource::Plain(password) => format!("plain:{:x}", Sha256::digest(password)),
+            PasswordSource::Env { env } => format!("env:{env}"),
+            PasswordSource::File { file } => format!("file:{}", file.display()),
+            PasswordSource::Command { command } => format!("command:{command:?}"),
+            PasswordSource::Hash { hash } => format!("hash:{:x}", Sha256::digest(hash)),
+        }
+    }
+
+    fn read(&self) -> anyhow::Result<String> {
+        match self {
+            PasswordSource::Plain(password) => Ok(password.clone()),
+            PasswordSource::Env { env } => {
+                std::env::var(env).with_context(|| format!("password environment variable '{}' is not set", env))
+            }
+            PasswordSource::File { file } => std::fs::read_to_string(file)
+                .map(|content| trim_line_ending(&content).to_string())
+                .with_context(|| format!("unable to read password file: {}", file.display())),
+            PasswordSource::Command { command } => run_password_command(command),
+            PasswordSource::Hash { .. } => anyhow::bail!("a pre-hashed password has no plaintext"),
+        }
+    }
+}
+
+impl fmt::Debug for PasswordSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordSource::Plain(_) => write!(f, "Plain(<redacted>)"),
+            PasswordSource::Env { env } => write!(f, "Env({env})"),
+            PasswordSource::File { file } => write!(f, "File({})", file.display()),
+            PasswordSource::Command { command } => write!(f, "Command({})", command.first().map_or("", String::as_str)),
+            PasswordSource::Hash { .. } => write!(f, "Hash(<redacted>)"),
+        }
+    }
+}
+
+impl fmt::Display for PasswordSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordSource::Plain(_) => write!(f, "inline"),
+            PasswordSource::Env { env } => write!(f, "env: {env}"),
+            PasswordSource::File { file } => write!(f, "file: {}", file.display()),
+            PasswordSource::Command { command } => write!(f, "command: {}", command.first().map_or("", String::as_str)),
+            PasswordSource::Hash { .. } => write!(f, "pre-hashed"),
+        }
+    }
+}
+
+fn run_password_command(command: &[String]) -> anyhow::Result<String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("password command must not be empty"))?;
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("unable to run password command '{}'", program))?;
+    if !output.status.success() {
+        // stdout may hold (part of) the secret, so only stderr is reported.
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "password command '{}' failed with status {}\n{}",
+            program,
+            output.status,
+            stderr.trim()
+        );
+    }
+    let stdout = String::from_utf8(output.stdout).context("password command output is not valid UTF-8")?;
+    Ok(trim_line_ending(&stdout).to_string())
+}
+
+fn trim_line_ending(value: &str) -> &str {
+    value.trim_end_matches(['\r', '\n'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(yaml: &str) -> anyhow::Result<PasswordSource> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    #[test]
+    fn deserializes_each_form() {
+        for (yaml, declaration) in [
+            ("{ env: DEV_PASSWORD }", "env:DEV_PASSWORD"),
+            ("{ file: secrets/dev.txt }", "file:secrets/dev.txt"),
+            ("{ command: [pass, show, dev] }", r#"command:["pass", "show", "dev"]"#),
+        ] {
+            assert_eq!(source(yaml).unwrap().declaration(), declaration);
+        }
+        let plain = source("hunter2").unwrap();
+        assert!(matches!(&plain, PasswordSource::Plain(password) if password == "hunter2"));
+        let hash = source("{ hash: \"$6$salt$abc\" }").unwrap();
+        assert_eq!(hash.hash(), Some("$6$salt$abc"));
+    }
+
+    #[test]
+    fn rejects_mixed_and_unknown_keys() {
+        for yaml in [
+            "{ env: X, file: Y }",
+            "{ hash: x, command: [y] }",
+            "{ vault: dev }",
+            "[a, b]",
+        ] {
+            let err = source(yaml).unwrap_err();
+            assert!(
+                err.to_string().contains("a plaintext password or one of"),
+                "{yaml}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn debug_and_display_never_show_the_secret() {
+        for (yaml, debug, display) in [
+            ("hunter2", "Plain(<redacted>)", "inline"),
+            ("{ hash: \"$6$salt$abc\" }", "Hash(<redacted>)", "pre-hashed"),
+            ("{ command: [pass, show, hunter2] }", "Command(pass)", "command: pass"),
+            ("{ env: DEV_PASSWORD }", "Env(DEV_PASSWORD)", "env: DEV_PASSWORD"),
+        ] {
+            let source = source(yaml).unwrap();
+            assert_eq!(format!("{source:?}"), debug);
+            assert_eq!(source.to_string(), display);
+        }
+    }
+
+    #[test]
+    fn an_unset_env_var_is_an_error() {
+        let err = source("{ env: WSLFORGE_TEST_UNSET_PASSWORD }")
+            .unwrap()
+            .reveal()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "password environment variable 'WSLFORGE_TEST_UNSET_PASSWORD' is not set"
+        );
+    }
+
+    #[test]
+    fn trims_the_trailing_newline_of_a_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("password.txt");
+        std::fs::write(&path, "correct horse \r\n").unwrap();
+
+        let source = PasswordSource::File { file: path };
+        assert_eq!(source.reveal().unwrap(), "correct horse ");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn trims_the_trailing_newline_of_a_command() {
+        let source = source("{ command: [sh, -c, \"printf 'battery staple\\n'\"] }").unwrap();
+        assert_eq!(source.reveal().unwrap(), "battery staple");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failing_command_reports_stderr_but_not_stdout() {
+        let source = source("{ command: [sh, -c, \"echo leaked-secret; echo vault locked >&2; exit 3\"] }").unwrap();
+
+        let err = format!("{:#}", source.reveal().unwrap_err());
+        assert!(err.contains("password command 'sh' failed"), "{err}");
+        assert!(err.contains("vault locked"), "{err}");
+        assert!(!err.contains("leaked-secret"), "{err}");
+    }
+}
@@ -0,0 +1,681 @@
+// `wslforge validate`: checks config files without touching WSL and reports every problem
+// at once, each with `file:line:column` and, when there is one, a suggestion.
+// - Per file: unknown keys (checked against the JSON Schema, so it follows the model),
+//   unknown `type` tags and malformed proxy URLs.
+// - After merging: anything serde still rejects in profiles or `timeouts`, missing files,
+//   hostnames and usernames breaking the rules in `wsl::validation`, and hostnames that
+//   name the same instance.
+//   Values whose env vars do not expand here (e.g. `%USERPROFILE%` on a Linux CI runner)
+//   are reported as warnings, not errors.
+use crate::config::loader::{self, Document, Merged, ROOT_KEYS};
+use crate::config::locate::{locate, Location};
+use crate::config::model::default_hostname;
+use crate::config::{Profile, Timeouts};
+use crate::wsl::helpers::expand_env_vars;
+use crate::wsl::validation::{distro_name_problem, hostname_problem, instance_key, username_problem};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    // Something that could not be checked here, e.g. a Windows env var on a Linux CI box.
+    Warning,
+}
+
+/// One problem found in the config.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub location: Option<Location>,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.location) {
+            (Some(file), Some(loc)) => write!(f, "{}:{}:{}: ", file.display(), loc.line, loc.column)?,
+            (Some(file), None) => write!(f, "{}: ", file.display())?,
+            _ => {}
+        }
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.message)?,
+            Severity::Warning => write!(f, "warning: {}", self.message)?,
+        }
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl Diagnostic {
+    fn new(message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            file: None,
+            location: None,
+            message,
+            suggestion: None,
+        }
+    }
+
+    fn at(document: &Document, keys: &[&str], message: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            file: Some(document.path.clone()),
+            location: locate(&document.raw, source_keys(document, keys)),
+            message,
+            suggestion: None,
+        }
+    }
+
+    fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
+    fn warning(mut self) -> Self {
+        self.severity = Severity::Warning;
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Validates the given config files (and everything they include).
+pub fn validate(paths: &[PathBuf]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut documents = Vec::new();
+    for path in paths {
+        if let Err(err) = loader::collect_documents(path, &mut Vec::new(), &mut documents) {
+            diagnostics.push(Diagnostic::new(format!("{err:#}")));
+        }
+    }
+
+    let known = KnownKeys::from_schema();
+    let mut flagged = Flagged::default();
+    for document in &documents {
+        check_document(document, &known, &mut flagged, &mut diagnostics);
+    }
+
+    match loader::merge_documents(&documents) {
+        Ok(merged) => check_merged(&documents, &merged, &flagged, &mut diagnostics),
+        Err(err) => diagnostics.push(Diagnostic::new(format!("{err:#}"))),
+    }
+    diagnostics.sort_by(|a, b| {
+        (&a.file, a.location.map(|l| (l.line, l.column))).cmp(&(&b.file, b.location.map(|l| (l.line, l.column))))
+    });
+    diagnostics
+}
+
+//
+// Per-file checks
+//
+
+// What already has a per-file diagnostic; the merged checks skip it so serde does not
+// report the same problem a second time, without a location.
+#[derive(Default)]
+struct Flagged {
+    defaults: bool,
+    profiles: BTreeSet<String>,
+}
+
+fn check_document(document: &Document, known: &KnownKeys, flagged: &mut Flagged, diagnostics: &mut Vec<Diagnostic>) {
+    for key in document.root.keys().filter_map(Value::as_str) {
+        if !ROOT_KEYS.contains(&key) {
+            diagnostics.push(
+                Diagnostic::at(document, &[key], format!("unknown top-level key `{}`", key))
+                    .with_suggestion(suggest(key, ROOT_KEYS.iter().copied())),
+            );
+        }
+    }
+
+    if let Some(Value::Mapping(defaults)) = document.root.get("defaults") {
+        let before = diagnostics.len();
+        check_profile(document, &["defaults"], "defaults", defaults, known, diagnostics);
+        flagged.defaults |= diagnostics.len() > before;
+    }
+    if let Some(Value::Mapping(profiles)) = document.root.get("profiles") {
+        for (name, profile) in profiles {
+            let (Some(name), Value::Mapping(profile)) = (name.as_str(), profile) else {
+                continue;
+            };
+            let before = diagnostics.len();
+            let label = format!("profile '{}'", name);
+            check_profile(document, &["profiles", name], &label, profile, known, diagnostics);
+            if diagnostics.len() > before {
+                flagged.profiles.insert(name.to_string());
+            }
+        }
+    }
+}
+
+fn check_profile(
+    document: &Document,
+    prefix: &[&str],
+    label: &str,
+    profile: &Mapping,
+    known: &KnownKeys,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for key in profile.keys().filter_map(Value::as_str) {
+        if !known.profile.iter().any(|k| k == key) {
+            diagnostics.push(
+                Diagnostic::at(
+                    document,
+                    &join(prefix, &[key]),
+                    format!("unknown field `{}` in {}", key, label),
+                )
+                .with_suggestion(suggest(key, known.profile.iter().map(String::as_str))),
+            );
+        }
+    }
+
+    for (field, variants) in [("image", &known.image), ("cloud_init", &known.cloud_init)] {
+        if let Some(Value::Mapping(source)) = profile.get(field) {
+            check_tagged(
+                document,
+                &join(prefix, &[field]),
+                field,
+                label,
+                source,
+                variants,
+                diagnostics,
+            );
+        }
+    }
+
+    if let Some(Value::Mapping(password)) = profile.get("password") {
+        for key in password.keys().filter_map(Value::as_str) {
+            if !known.password.iter().any(|k| k == key) {
+                diagnostics.push(
+                    Diagnostic::at(
+                        document,
+                        &join(prefix, &["password", key]),
+                        format!("unknown password source `{}` in {}", key, label),
+                    )
+                    .with_suggestion(suggest(key, known.password.iter().map(String::as_str))),
+                );
+            }
+        }
+        if password.len() != 1 {
+            diagnostics.push(Diagnostic::at(
+                document,
+                &join(prefix, &["password"]),
+                format!("`password` in {} must have exactly one source key", label),
+            ));
+        }
+    }
+
+    for field in ["http_proxy", "https_proxy"] {
+        if let Some(Value::String(raw)) = profile.get(field) {
+            if let Err(err) = Url::parse(raw) {
+                diagnostics.push(
+                    Diagnostic::at(
+                        document,
+                        &join(prefix, &[field]),
+                        format!("invalid URL for `{}` in {}: {}", field, label, err),
+                    )
+                    .with_suggestion(Some("use a full URL such as `http://proxy.local:8080`".into())),
+                );
+            }
+        }
+    }
+}
+
+fn check_tagged(
+    document: &Document,
+    path: &[&str],
+    field: &str,
+    label: &str,
+    source: &Mapping,
+    variants: &BTreeMap<String, Vec<String>>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let allowed: Vec<&str> = match source.get("type") {
+        Some(Value::String(tag)) => match variants.get(tag) {
+            Some(keys) => keys.iter().map(String::as_str).collect(),
+            None => {
+                diagnostics.push(
+                    Diagnostic::at(
+                        document,
+                        &join(path, &["type"]),
+                        format!("unknown `{}` type `{}` in {}", field, tag, label),
+                    )
+                    .with_suggestion(suggest(tag, variants.keys().map(String::as_str))),
+                );
+                return;
+            }
+        },
+        // Without a tag (e.g. a partial override of an inherited block) any variant's keys go.
+        _ => variants.values().flatten().map(String::as_str).collect(),
+    };
+
+    for key in source.keys().filter_map(Value::as_str) {
+        if !allowed.contains(&key) {
+            diagnostics.push(
+                Diagnostic::at(
+                    document,
+                    &join(path, &[key]),
+                    format!("unknown field `{}` in `{}` of {}", key, field, label),
+                )
+                .with_suggestion(suggest(key, allowed.iter().copied())),
+            );
+        }
+    }
+}
+
+//
+// Checks on the merged config
+//
+
+fn check_merged(documents: &[Document], merged: &Merged, flagged: &Flagged, diagnostics: &mut Vec<Diagnostic>) {
+    check_timeouts(documents, merged, diagnostics);
+    let Some(Value::Mapping(profiles)) = merged.root.get("profiles") else {
+        diagnostics.push(Diagnostic::new("no profiles defined".into()));
+        return;
+    };
+    let mut hostnames: BTreeMap<String, (String, String)> = BTreeMap::new();
+    for (name, value) in profiles {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        let at = |fields: &[&str], message: String| locate_profile(documents, name, fields, message);
+
+        // A problem in `defaults` is inherited by every profile; don't report it again for each.
+        if !flagged.defaults && !flagged.profiles.contains(name) {
+            if let Err(err) = serde_yaml::from_value::<Profile>(value.clone()) {
+                diagnostics.push(at(&[], format!("invalid profile '{}': {}", name, err)));
+            }
+        }
+
+        let raw_hostname = value
+            .get("hostname")
+            .and_then(Value::as_str)
+            .map_or_else(default_hostname, str::to_string);
+        match expand_env_vars(&raw_hostname) {
+            Ok(hostname) => {
+                if let Some(problem) = distro_name_problem(&hostname).or_else(|| hostname_problem(&hostname)) {
+                    diagnostics.push(at(
+                        &["hostname"],
+                        format!("invalid hostname '{}': {}", hostname, problem),
+                    ));
+                }
+                match hostnames.get(&instance_key(&hostname)) {
+                    Some((other, other_hostname)) => diagnostics.push(
+                        at(
+                            &["hostname"],
+                            format!(
+                                "hostname '{}' of profile '{}' is the same WSL instance as '{}' of profile '{}'",
+                                hostname, name, other_hostname, other
+                            ),
+                        )
+                        .with_suggestion(Some(
+                            "give each profile a unique `hostname`; WSL ignores case in instance names".into(),
+                        )),
+                    ),
+                    None => {
+                        hostnames.insert(instance_key(&hostname), (name.to_string(), hostname));
+                    }
+                }
+            }
+            Err(err) => diagnostics.push(at(&["hostname"], format!("cannot check hostname: {err:#}")).warning()),
+        }
+
+        if let Some(username) = value.get("username").and_then(Value::as_str) {
+            match expand_env_vars(username) {
+                Ok(username) => {
+                    if let Some(problem) = username_problem(&username) {
+                        diagnostics.push(at(
+                            &["username"],
+                            format!("invalid username '{}': {}", username, problem),
+                        ));
+                    }
+                }
+                Err(err) => diagnostics.push(at(&["username"], format!("cannot check username: {err:#}")).warning()),
+            }
+        }
+
+        for (fields, raw_path) in referenced_files(value) {
+            match expand_env_vars(raw_path) {
+                Ok(path) if !Path::new(&path).exists() => {
+                    diagnostics.push(at(fields, format!("file not found: {}", path)).with_suggestion(Some(
+                        "relative paths are resolved against the config file that declares them".into(),
+                    )))
+                }
+                Ok(_) => {}
+                Err(err) => diagnostics.push(at(fields, format!("cannot check file: {err:#}")).warning()),
+            }
+        }
+    }
+}
+
+// Each top-level `timeouts` entry is deserialized on its own, so a bad duration is reported
+// at its key rather than once for the whole map without a location.
+fn check_timeouts(documents: &[Document], merged: &Merged, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(timeouts) = merged.root.get("timeouts") else {
+        return;
+    };
+    let Value::Mapping(entries) = timeouts else {
+        if let Err(err) = serde_yaml::from_value::<Timeouts>(timeouts.clone()) {
+            diagnostics.push(locate_root(
+                documents,
+                &["timeouts"],
+                format!("invalid `timeouts`: {}", err),
+            ));
+        }
+        return;
+    };
+    for (key, value) in entries {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let entry = Mapping::from_iter([(Value::from(key), value.clone())]);
+        if let Err(err) = serde_yaml::from_value::<Timeouts>(Value::Mapping(entry)) {
+            diagnostics.push(locate_root(
+                documents,
+                &["timeouts", key],
+                format!("invalid `timeouts.{}`: {}", key, err),
+            ));
+        }
+    }
+}
+
+// Locates a top-level key in the last file that writes it.
+fn locate_root(documents: &[Document], keys: &[&str], message: String) -> Diagnostic {
+    documents
+        .iter()
+        .rev()
+        .filter(|doc| !doc.single_profile)
+        .find(|doc| locate(&doc.raw, keys).is_some())
+        .map_or_else(
+            || Diagnostic::new(message.clone()),
+            |doc| Diagnostic::at(doc, keys, message.clone()),
+        )
+}
+
+// The file paths a merged profile refers to, read from the raw value so they can be
+// checked even when the profile fails to deserialize for another reason.
+fn referenced_files(profile: &Value) -> Vec<(&'static [&'static str], &str)> {
+    let mut files: Vec<(&'static [&'static str], &str)> = Vec::new();
+    for field in ["image", "cloud_init"] {
+        let source = &profile[field];
+        if source["type"].as_str() == Some("file") {
+            if let Some(path) = source["path"].as_str() {
+                let fields: &'static [&'static str] = if field == "image" {
+                    &["image", "path"]
+                } else {
+                    &["cloud_init", "path"]
+                };
+                files.push((fields, path));
+            }
+        }
+    }
+    if let Some(file) = profile["password"]["file"].as_str() {
+        files.push((&["password", "file"], file));
+    }
+    files
+}
+
+// Locates `profiles/<name>/<fields>` in the last file that writes it, falling back to the
+// profile's own key (the field may come from `defaults` or a parent profile).
+fn locate_profile(documents: &[Document], name: &str, fields: &[&str], message: String) -> Diagnostic {
+    let mut keys = vec!["profiles", name];
+    keys.extend_from_slice(fields);
+
+    let defining = || {
+        documents
+            .iter()
+            .rev()
+            .filter(|doc| matches!(doc.root.get("profiles"), Some(Value::Mapping(p)) if p.contains_key(name)))
+    };
+    let document = defining()
+        .find(|doc| locate(&doc.raw, source_keys(doc, &keys)).is_some())
+        .or_else(|| defining().next());
+    match document {
+        Some(document) => {
+            let mut diagnostic = Diagnostic::at(document, &keys, message);
+            if diagnostic.location.is_none() {
+                diagnostic.location = locate(&document.raw, source_keys(document, &keys[..2]));
+            }
+            diagnostic
+        }
+        None => Diagnostic::new(message),
+    }
+}
+
+// Single-profile files are wrapped as `profiles: { <name>: ... }` when loaded, but their
+// keys sit at the top level of the file.
+fn source_keys<'a>(document: &Document, keys: &'a [&'a str]) -> &'a [&'a str] {
+    if document.single_profile && keys.first() == Some(&"profiles") {
+        keys.get(2..).unwrap_or_default()
+    } else {
+        keys
+    }
+}
+
+fn join<'a>(prefix: &[&'a str], keys: &[&'a str]) -> Vec<&'a str> {
+    prefix.iter().chain(keys).copied().collect()
+}
+
+//
+// Known keys, taken from the JSON Schema so they follow the model
+//
+
+struct KnownKeys {
+    profile: Vec<String>,
+    image: BTreeMap<String, Vec<String>>,
+    cloud_init: BTreeMap<String, Vec<String>>,
+    password: Vec<String>,
+}
+
+impl KnownKeys {
+    fn from_schema() -> Self {
+        let schema = schemars::schema_for!(Profile);
+        let schema = schema.as_value();
+        let defs = &schema["$defs"];
+        Self {
+            profile: property_names(schema),
+            image: tagged_variants(&defs["ImageSource"]),
+            cloud_init: tagged_variants(&defs["CloudInitSource"]),
+            password: defs["PasswordSource"]["anyOf"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(property_names)
+                .collect(),
+        }
+    }
+}
+
+fn property_names(schema: &serde_json::Value) -> Vec<String> {
+    schema["properties"]
+        .as_object()
+        .map(|properties| properties.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn tagged_variants(schema: &serde_json::Value) -> BTreeMap<String, Vec<String>> {
+    schema["oneOf"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|variant| {
+            let tag = variant["properties"]["type"]["const"].as_str()?;
+            Some((tag.to_string(), property_names(variant)))
+        })
+        .collect()
+}
+
+//
+// Suggestions
+//
+
+fn suggest<'a>(unknown: &str, candidates: impl Iterator<Item = &'a str> + Clone) -> Option<String> {
+    let closest = candidates
+        .clone()
+        .map(|candidate| (edit_distance(unknown, candidate), candidate))
+        .min_by_key(|(distance, _)| *distance);
+    match closest {
+        Some((distance, candidate)) if distance <= 2.max(unknown.len() / 3) => {
+            Some(format!("did you mean `{}`?", candidate))
+        }
+        _ => {
+            let all: Vec<&str> = candidates.collect();
+            (!all.is_empty()).then(|| format!("expected one of: {}", all.join(", ")))
+        }
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str, content: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn rendered(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn reports_problems_in_included_files_at_their_location() {
+        let dir = TempDir::new().unwrap();
+        let base = write(
+            &dir,
+            "base.yaml",
+            "profiles:\n  dev:\n    usernmae: me\n    image: { type: fiel, path: rootfs.tar }\n",
+        );
+        let main = write(
+            &dir,
+            "main.yaml",
+            "include: [base.yaml]\nprofiles:\n  dev:\n    hostname: dev\n  other:\n    hostname: DEV\n",
+        );
+
+        let diagnostics = validate(std::slice::from_ref(&main));
+        assert_eq!(
+            rendered(&diagnostics),
+            [
+                format!(
+                    "{}:3:5: error: unknown field `usernmae` in profile 'dev'\n  help: did you mean `username`?",
+                    base.display()
+                ),
+                format!(
+                    "{}:4:14: error: unknown `image` type `fiel` in profile 'dev'\n  help: did you mean `file`?",
+                    base.display()
+                ),
+                format!(
+                    "{}:6:5: error: hostname 'DEV' of profile 'other' is the same WSL instance as 'dev' of \
+                     profile 'dev'\n  help: give each profile a unique `hostname`; WSL ignores case in instance \
+                     names",
+                    main.display()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_files_and_bad_names_on_the_profile_that_declares_them() {
+        let dir = TempDir::new().unwrap();
+        let path = write(
+            &dir,
+            "config.yaml",
+            "profiles:\n  dev:\n    hostname: bad_host\n    username: Root\n    cloud_init: { type: file, path: \
+             missing.yaml }\n",
+        );
+
+        let diagnostics = validate(std::slice::from_ref(&path));
+        let messages = rendered(&diagnostics);
+        assert_eq!(diagnostics.len(), 3, "{messages:#?}");
+        assert!(messages[0].starts_with(&format!("{}:3:5: error: invalid hostname 'bad_host'", path.display())));
+        assert!(messages[1].starts_with(&format!("{}:4:5: error: invalid username 'Root'", path.display())));
+        assert!(messages[2].starts_with(&format!("{}:5:31: error: file not found: ", path.display())));
+    }
+
+    #[test]
+    fn single_profile_files_are_located_at_their_top_level() {
+        let dir = TempDir::new().unwrap();
+        let path = write(
+            &dir,
+            "single.yaml",
+            "hostname: solo\nimage:\n  type: distro\n  nmae: Ubuntu\n",
+        );
+
+        let diagnostics = validate(&[path]);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, Some(Location { line: 4, column: 3 }));
+    }
+
+    #[test]
+    fn unexpandable_env_vars_are_warnings() {
+        let dir = TempDir::new().unwrap();
+        let path = write(
+            &dir,
+            "config.yaml",
+            "profiles:\n  dev:\n    image: { type: file, path: \"%WSLFORGE_TEST_UNSET%/rootfs.tar\" }\n",
+        );
+
+        let diagnostics = validate(&[path]);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(!diagnostics[0].is_error(), "{}", diagnostics[0]);
+    }
+
+    #[test]
+    fn reports_bad_top_level_timeouts_at_their_key() {
+        let dir = TempDir::new().unwrap();
+        let path = write(
+            &dir,
+            "config.yaml",
+            "timeouts:\n  import: 10m\n  install: 5x\nprofiles:\n  dev:\n    hostname: dev\n",
+        );
+
+        let diagnostics = validate(std::slice::from_ref(&path));
+        let messages = rendered(&diagnostics);
+        assert_eq!(diagnostics.len(), 1, "{messages:#?}");
+        assert!(
+            messages[0].starts_with(&format!("{}:3:3: error: invalid `timeouts.install`: ", path.display())),
+            "{}",
+            messages[0]
+        );
+    }
+
+    #[test]
+    fn suggests_the_closest_key_or_lists_the_candidates() {
+        let keys = ["hostname", "username", "image"];
+        assert_eq!(
+            suggest("hostnme", keys.iter().copied()).as_deref(),
+            Some("did you mean `hostname`?")
+        );
+        assert_eq!(
+            suggest("zzzzzzzz", keys.iter().copied()).as_deref(),
+            Some("expected one of: hostname, username, image")
+        );
+    }
+}
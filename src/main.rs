@@ -1,37 +1,370 @@
 use clap::Parser;
 use log::LevelFilter;
-use wslforge::{cli::Args, config, wsl::WslManager};
+use std::io::{IsTerminal, Write};
+use std::process::ExitCode;
+use std::sync::Arc;
+use wslforge::{
+    cli::{Args, Command, SnapshotAction},
+    config::{self, RootConfig},
+    redact,
+    wsl::{
+        self, EngineOptions, Interrupted, Plan, RecordingRunner, ReplayRunner, ResolvedProfile, StateStore, WslManager,
+    },
+};
 
-fn main() -> anyhow::Result<()> {
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {}", redact::text(&format!("{err:?}")));
+            // 128 + SIGINT, as shells report a process stopped by Ctrl-C.
+            if err.downcast_ref::<Interrupted>().is_some() {
+                return ExitCode::from(130);
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> anyhow::Result<ExitCode> {
     let args = Args::parse();
 
     if args.print_config {
         println!("{}", config::EXAMPLE_CONFIG);
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
     }
 
     init_logger(args.verbose);
-    ensure_windows()?;
+    wsl::install_interrupt_handler()?;
+
+    match args.command() {
+        Command::Schema => {
+            println!("{}", config::json_schema()?);
+            return Ok(ExitCode::SUCCESS);
+        }
+        Command::Validate => return Ok(validate(&args)),
+        _ => {}
+    }
 
     let cfg = config::load_yaml(&args.config)?;
-    log::debug!("📋 Loaded config from {}", args.config.display());
-    let manager = WslManager::new(args.dry_run, args.debug);
+    for path in &args.config {
+        log::debug!("📋 Loaded config from {}", path.display());
+    }
+
+    match args.command() {
+        Command::Apply {
+            keep_going,
+            jobs,
+            no_rollback,
+            backup,
+        } => return apply(&args, &cfg, keep_going, jobs.get(), !no_rollback, backup),
+        Command::Plan {
+            json,
+            detailed_exitcode,
+        } => return plan(&args, &cfg, json, detailed_exitcode),
+        Command::Destroy {
+            profiles,
+            all,
+            yes,
+            force,
+        } => return destroy(&args, &cfg, &profiles, all, yes, force),
+        Command::Snapshot { action, profile, name } => snapshot(&args, &cfg, action, profile, name)?,
+        Command::Restore {
+            profile,
+            snapshot,
+            force,
+        } => restore(&args, &cfg, &profile, snapshot.as_deref(), force)?,
+        Command::List => wsl::print_profile_list(&cfg.profiles),
+        Command::Status => status(&args, &cfg)?,
+        Command::Render { profile } => render(&args, &cfg, &profile)?,
+        Command::Schema | Command::Validate => unreachable!("handled before loading the config"),
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// The environment checks and `wsl --update` run once, before any profile starts.
+fn apply(
+    args: &Args,
+    cfg: &RootConfig,
+    keep_going: bool,
+    jobs: usize,
+    rollback: bool,
+    backup: bool,
+) -> anyhow::Result<ExitCode> {
+    // A transcript is one ordered list of commands, which parallel profiles would shuffle.
+    let jobs = if jobs > 1 && (args.record.is_some() || args.replay.is_some()) {
+        log::warn!("⚠️  --record and --replay run profiles one at a time; ignoring --jobs");
+        1
+    } else {
+        jobs
+    };
+    let manager = connect_with(args, cfg, args.dry_run, |options| options.inherit_output = jobs == 1)?
+        .with_rollback(rollback)
+        .with_backups(backup);
 
+    manager.validate_profiles(&cfg.profiles)?;
     manager.validate_environment()?;
-    for (profile_name, profile) in &cfg.profiles {
-        manager.create_instance(profile_name, profile)?;
+    let mut report = manager.apply_profiles(&cfg.profiles, jobs, keep_going);
+
+    wsl::print_apply_summary(&report, cfg.profiles.len());
+    let mut interrupted = None;
+    for result in &mut report.results {
+        match result.error.take() {
+            Some(err) if err.is::<Interrupted>() => interrupted = Some(err),
+            Some(err) => eprintln!(
+                "Error in profile '{}': {}",
+                result.profile,
+                redact::text(&format!("{err:?}"))
+            ),
+            None => {}
+        }
+    }
+    if let Some(err) = interrupted {
+        return Err(err);
+    }
+    Ok(if report.has_failures() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn plan(args: &Args, cfg: &RootConfig, json: bool, detailed_exitcode: bool) -> anyhow::Result<ExitCode> {
+    let manager = connect(args, cfg, true)?;
+
+    manager.validate_profiles(&cfg.profiles)?;
+    manager.validate_environment()?;
+    let plan = Plan {
+        entries: cfg
+            .profiles
+            .iter()
+            .map(|(profile_name, profile)| manager.plan_instance(profile_name, profile))
+            .collect(),
+    };
+
+    if json {
+        println!("{}", redact::text(&serde_json::to_string_pretty(&plan)?));
+    } else {
+        wsl::print_plan(&plan);
+    }
+
+    let code = if plan.has_errors() {
+        1
+    } else if detailed_exitcode && plan.has_changes() {
+        2
+    } else {
+        0
+    };
+    Ok(ExitCode::from(code))
+}
+
+fn validate(args: &Args) -> ExitCode {
+    let diagnostics = config::validate(&args.config);
+    for diagnostic in &diagnostics {
+        println!("{}", redact::text(&diagnostic.to_string()));
+    }
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+    if errors == 0 {
+        println!("✅ Config is valid ({} warning(s))", warnings);
+        ExitCode::SUCCESS
+    } else {
+        println!("⛔ {} error(s), {} warning(s)", errors, warnings);
+        ExitCode::FAILURE
+    }
+}
+
+// Every profile is attempted even when one fails; the exit code tells whether all went.
+fn destroy(
+    args: &Args,
+    cfg: &RootConfig,
+    profile_names: &[String],
+    all: bool,
+    yes: bool,
+    force: bool,
+) -> anyhow::Result<ExitCode> {
+    let profile_names: Vec<&String> = match all {
+        true => cfg.profiles.keys().collect(),
+        false => profile_names.iter().collect(),
+    };
+    let mut targets = Vec::new();
+    for profile_name in profile_names {
+        targets.push((profile_name, cfg.profile(profile_name)?));
     }
+    if targets.is_empty() {
+        anyhow::bail!("no profiles to destroy");
+    }
+    let manager = connect(args, cfg, args.dry_run)?;
+
+    if !args.dry_run && !yes && !confirm_destroy(&targets)? {
+        println!("Nothing destroyed.");
+        return Ok(ExitCode::SUCCESS);
+    }
+    let mut failed = false;
+    for (profile_name, profile) in targets {
+        match manager.destroy_instance(profile_name, profile, force) {
+            Ok(destroyed) if args.dry_run => wsl::print_destroy_plan(&destroyed),
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!(
+                    "Error in profile '{}': {}",
+                    profile_name,
+                    redact::text(&format!("{err:?}"))
+                );
+                failed = true;
+            }
+        }
+    }
+    Ok(if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+}
+
+// Refuses rather than asks when nobody is there to answer. Ctrl-C at the prompt is a no.
+fn confirm_destroy(targets: &[(&String, &config::Profile)]) -> anyhow::Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        anyhow::bail!("refusing to destroy without confirmation; pass --yes");
+    }
+    println!("This unregisters these WSL instances and deletes their install dirs and cloud-init files:");
+    for (profile_name, profile) in targets {
+        let hostname = match ResolvedProfile::resolve(profile_name, profile) {
+            Ok(resolved) => resolved.hostname,
+            Err(_) => profile.hostname.clone(),
+        };
+        println!("  {} ({})", hostname, profile_name);
+    }
+    print!("Continue? [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    let read = std::io::stdin().read_line(&mut answer);
+    // The Ctrl-C handler only raises a flag, so a Ctrl-C at the prompt lands here.
+    if wsl::is_interrupted() {
+        println!();
+        return Err(Interrupted.into());
+    }
+    read?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes" | "YES"))
+}
 
+fn snapshot(
+    args: &Args,
+    cfg: &RootConfig,
+    action: Option<SnapshotAction>,
+    profile: Option<String>,
+    name: Option<String>,
+) -> anyhow::Result<()> {
+    let manager = connect(args, cfg, args.dry_run)?;
+    match action {
+        Some(SnapshotAction::List { profile: profile_name }) => {
+            let profile = cfg.profile(&profile_name)?;
+            wsl::print_snapshots(&manager.list_snapshots(&profile_name, profile)?);
+        }
+        Some(SnapshotAction::Rm {
+            profile: profile_name,
+            snapshot,
+        }) => manager.remove_snapshot(&profile_name, cfg.profile(&profile_name)?, &snapshot)?,
+        None => {
+            // clap requires the profile when there is no subcommand.
+            let profile_name = profile.unwrap_or_default();
+            let profile = cfg.profile(&profile_name)?;
+            if let Some(snapshot) = manager.snapshot_instance(&profile_name, profile, name.as_deref())? {
+                println!("{}", snapshot.name);
+            }
+        }
+    }
     Ok(())
 }
 
-fn ensure_windows() -> anyhow::Result<()> {
-    if !cfg!(target_os = "windows") {
-        anyhow::bail!("wslforge is Windows-only (target_os=windows required)");
+// Without a snapshot name, lists what there is to restore.
+fn restore(
+    args: &Args,
+    cfg: &RootConfig,
+    profile_name: &str,
+    snapshot: Option<&str>,
+    force: bool,
+) -> anyhow::Result<()> {
+    let profile = cfg.profile(profile_name)?;
+    let manager = connect(args, cfg, args.dry_run)?;
+    match snapshot {
+        Some(snapshot) => manager.restore_instance(profile_name, profile, snapshot, force),
+        None => {
+            wsl::print_snapshots(&manager.list_snapshots(profile_name, profile)?);
+            Ok(())
+        }
+    }
+}
+
+fn status(args: &Args, cfg: &RootConfig) -> anyhow::Result<()> {
+    let manager = connect(args, cfg, args.dry_run)?;
+
+    let instances = manager.list_instances()?;
+    let mut rows = Vec::new();
+    for (profile_name, profile) in &cfg.profiles {
+        let resolved = ResolvedProfile::resolve(profile_name, profile)?;
+        let instance = instances
+            .iter()
+            .find(|instance| instance.name.eq_ignore_ascii_case(&resolved.hostname))
+            .cloned();
+        rows.push((profile_name.clone(), resolved.hostname, instance));
     }
+    wsl::print_status(&rows);
     Ok(())
 }
 
+fn render(args: &Args, cfg: &RootConfig, profile_name: &str) -> anyhow::Result<()> {
+    let profile = cfg.profile(profile_name)?;
+    let manager = manager(args, cfg, args.dry_run)?;
+    match manager.render_cloud_init(profile_name, profile)? {
+        Some(rendered) => print!("{}", rendered),
+        None => anyhow::bail!("profile '{}' has no cloud_init configured", profile_name),
+    }
+    Ok(())
+}
+
+fn manager(args: &Args, cfg: &RootConfig, dry_run: bool) -> anyhow::Result<WslManager> {
+    manager_with(args, cfg, dry_run, |_| {})
+}
+
+// `--wsl-exe` (or `WSLFORGE_WSL_EXE`) wins over `wsl_exe` in the config; `adjust` gets the
+// last word on the engine options.
+fn manager_with(
+    args: &Args,
+    cfg: &RootConfig,
+    dry_run: bool,
+    adjust: impl FnOnce(&mut EngineOptions),
+) -> anyhow::Result<WslManager> {
+    let mut options = EngineOptions::default();
+    if let Some(wsl_exe) = args.wsl_exe.clone().or_else(|| cfg.wsl_exe.clone()) {
+        options.wsl_exe = wsl_exe;
+    }
+    options.timeouts = cfg.timeouts.clone();
+    if let Some(path) = &args.record {
+        options.runner = Arc::new(RecordingRunner::new(path));
+    } else if let Some(path) = &args.replay {
+        options.runner = Arc::new(ReplayRunner::load(path)?);
+    }
+    adjust(&mut options);
+    let state = match &args.state {
+        Some(path) => path.clone(),
+        None => StateStore::default_path()?,
+    };
+    Ok(WslManager::with_engine(args.engine, &options, dry_run, args.debug).with_state(StateStore::new(&state)))
+}
+
+// A manager for commands that talk to WSL, once the selected engine is known to work here.
+fn connect(args: &Args, cfg: &RootConfig, dry_run: bool) -> anyhow::Result<WslManager> {
+    connect_with(args, cfg, dry_run, |_| {})
+}
+
+fn connect_with(
+    args: &Args,
+    cfg: &RootConfig,
+    dry_run: bool,
+    adjust: impl FnOnce(&mut EngineOptions),
+) -> anyhow::Result<WslManager> {
+    let manager = manager_with(args, cfg, dry_run, adjust)?;
+    manager.ensure_available()?;
+    Ok(manager)
+}
+
 fn init_logger(verbosity: u8) {
     let level = match verbosity {
         0 => LevelFilter::Warn,
@@ -39,8 +372,22 @@ fn init_logger(verbosity: u8) {
         _ => LevelFilter::Debug,
     };
 
+    // Every log line goes through `redact`, whichever module emitted it.
     env_logger::Builder::new()
         .filter_level(level)
-        .format_timestamp(None)
+        .format(|buf, record| {
+            let style = buf.default_level_style(record.level());
+            let prefix = wsl::log_prefix()
+                .map(|profile| format!("[{profile}] "))
+                .unwrap_or_default();
+            writeln!(
+                buf,
+                "[{style}{:<5}{style:#} {}] {}{}",
+                record.level(),
+                record.target(),
+                prefix,
+                redact::text(&record.args().to_string())
+            )
+        })
         .init();
 }
@@ -0,0 +1,37 @@
+use crate::wsl::engine::CreateOutcome;
+use crate::wsl::runner::Interrupted;
+use std::time::Duration;
+
+// What `apply` did with one profile. `error` is set exactly when `outcome` is `Failed`.
+#[derive(Debug)]
+pub struct ApplyResult {
+    pub profile: String,
+    pub hostname: String,
+    pub outcome: CreateOutcome,
+    pub duration: Duration,
+    pub error: Option<anyhow::Error>,
+}
+
+#[derive(Debug, Default)]
+pub struct ApplyReport {
+    pub results: Vec<ApplyResult>,
+}
+
+impl ApplyReport {
+    pub fn has_failures(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.outcome == CreateOutcome::Failed)
+    }
+
+    // Whether Ctrl-C, rather than a failing profile, ended the run.
+    pub fn was_interrupted(&self) -> bool {
+        self.results
+            .iter()
+            .any(|result| result.error.as_ref().is_some_and(|err| err.is::<Interrupted>()))
+    }
+
+    pub fn count(&self, outcome: CreateOutcome) -> usize {
+        self.results.iter().filter(|result| result.outcome == outcome).count()
+    }
+}
@@ -1,13 +1,17 @@
-use super::helpers::{expand_env_vars, hash_password_sha512, resolve_userprofile_dir};
-use crate::config::{CloudInitSource, Profile};
+use super::helpers::{hash_password_sha512, resolve_userprofile_dir};
+use crate::config::CloudInitSource;
+use crate::redact;
+use crate::wsl::resolve::ResolvedProfile;
 use log::{debug, info, warn};
 use minijinja::Environment;
+use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 
-pub fn prepare_cloud_init(profile: &Profile, dry_run: bool, debug: bool) -> anyhow::Result<()> {
+// Returns the user-data file it wrote, if any.
+pub fn prepare_cloud_init(profile: &ResolvedProfile, dry_run: bool, debug: bool) -> anyhow::Result<Option<PathBuf>> {
     let Some(source) = &profile.cloud_init else {
         info!("☁️ Cloud-init: not configured");
-        return Ok(());
+        return Ok(None);
     };
 
     let target_file = create_cloud_init_target(&profile.hostname, dry_run)?;
@@ -17,7 +21,15 @@ pub fn prepare_cloud_init(profile: &Profile, dry_run: bool, debug: bool) -> anyh
     let rendered = render_cloud_init(&raw, profile)?;
     debug!("☁️ Cloud-init rendered:\n{}", rendered);
     write_cloud_init(&target_file, &rendered, &profile.hostname, dry_run, debug)?;
-    Ok(())
+    Ok((!dry_run).then_some(target_file))
+}
+
+pub fn render_profile_cloud_init(profile: &ResolvedProfile) -> anyhow::Result<Option<String>> {
+    let Some(source) = &profile.cloud_init else {
+        return Ok(None);
+    };
+    let raw = load_cloud_init_source(source)?;
+    render_cloud_init(&raw, profile).map(Some)
 }
 
 // Determine the target path for the cloud-init user-data file based on the hostname.
@@ -26,22 +38,27 @@ fn create_cloud_init_target(hostname: &str, dry_run: bool) -> anyhow::Result<Pat
         info!("🧪 Dry run: cloud-init target would be created at: {}", hostname);
         return Ok(PathBuf::from(format!("{}.user-data", hostname)));
     }
+    let target_file = user_data_path(hostname)?;
+    if let Some(target_dir) = target_file.parent() {
+        std::fs::create_dir_all(target_dir)?;
+    }
+    Ok(target_file)
+}
+
+// `%USERPROFILE%\.cloud-init\<hostname>.user-data`, where WSL looks for an instance's user-data.
+pub(crate) fn user_data_path(hostname: &str) -> anyhow::Result<PathBuf> {
     let userprofile = resolve_userprofile_dir()?;
-    let target_dir = userprofile.join(".cloud-init");
-    std::fs::create_dir_all(&target_dir)?;
-    Ok(target_dir.join(format!("{}.user-data", hostname)))
+    Ok(userprofile.join(".cloud-init").join(format!("{}.user-data", hostname)))
 }
 
 fn load_cloud_init_source(source: &CloudInitSource) -> anyhow::Result<String> {
     match source {
         CloudInitSource::File { path } => {
-            let expanded = expand_env_vars(&path.to_string_lossy())?;
-            let expanded_path = PathBuf::from(expanded);
-            if !expanded_path.exists() {
-                anyhow::bail!("cloud-init user-data file not found: {}", expanded_path.display());
+            if !path.exists() {
+                anyhow::bail!("cloud-init user-data file not found: {}", path.display());
             }
-            info!("☁️ Cloud-init source: {}", expanded_path.display());
-            std::fs::read_to_string(expanded_path).map_err(Into::into)
+            info!("☁️ Cloud-init source: {}", path.display());
+            std::fs::read_to_string(path).map_err(Into::into)
         }
         CloudInitSource::Inline { content } => {
             info!("☁️ Cloud-init source: inline content");
@@ -66,7 +83,33 @@ fn write_cloud_init(
     Ok(())
 }
 
-fn render_cloud_init(raw: &str, profile: &Profile) -> anyhow::Result<String> {
+fn render_cloud_init(raw: &str, profile: &ResolvedProfile) -> anyhow::Result<String> {
+    let password_hash = match &profile.password {
+        Some(source) => match source.hash() {
+            Some(hash) => Some(hash.to_string()),
+            None => Some(hash_password_sha512(&source.reveal()?)?),
+        },
+        None => None,
+    };
+    render_template(raw, profile, password_hash)
+}
+
+// Renders with a digest of how the password is declared in place of its salted crypt(3)
+// hash, so the result only changes when the template, the profile or the password's
+// declaration does. The password itself is never read. For fingerprints only.
+pub(crate) fn render_profile_cloud_init_stable(profile: &ResolvedProfile) -> anyhow::Result<Option<String>> {
+    let Some(source) = &profile.cloud_init else {
+        return Ok(None);
+    };
+    let raw = load_cloud_init_source(source)?;
+    let password_hash = profile
+        .password
+        .as_ref()
+        .map(|source| format!("sha256:{:x}", Sha256::digest(source.declaration())));
+    render_template(&raw, profile, password_hash).map(Some)
+}
+
+fn render_template(raw: &str, profile: &ResolvedProfile, password_hash: Option<String>) -> anyhow::Result<String> {
     let mut env = Environment::new();
     env.add_template("cloud-init.user-data", raw)
         .map_err(|e| anyhow::anyhow!("cloud-init template parse error: {e}"))?;
@@ -75,11 +118,6 @@ fn render_cloud_init(raw: &str, profile: &Profile) -> anyhow::Result<String> {
         .get_template("cloud-init.user-data")
         .map_err(|e| anyhow::anyhow!("cloud-init template load error: {e}"))?;
 
-    let password_hash = match profile.password.as_deref() {
-        Some(password) => Some(hash_password_sha512(password)?),
-        None => None,
-    };
-
     template
         .render(minijinja::context! { profile => profile, password_hash => password_hash })
         .map_err(|e| anyhow::anyhow!("cloud-init template render error: {e}"))
@@ -94,7 +132,7 @@ fn debug_cloud_init(rendered: &str, hostname: &str) {
         }
     };
 
-    if let Err(err) = std::fs::write(&debug_path, rendered) {
+    if let Err(err) = std::fs::write(&debug_path, redact::text(rendered)) {
         warn!("☁️ Cloud-init debug copy skipped (write error): {}", err);
     } else {
         info!("☁️ Cloud-init debug copy: {}", debug_path.display());
@@ -1,4 +1,5 @@
-use crate::wsl::engine::WslEngine;
+use crate::config::Timeouts;
+use crate::wsl::engine::{CommandOutput, InstanceInfo, WslEngine};
 
 pub struct ApiEngine;
 
@@ -9,7 +10,7 @@ impl ApiEngine {
 }
 
 impl WslEngine for ApiEngine {
-    fn instance_exists(&self, _name: &str) -> anyhow::Result<bool> {
+    fn list_instances(&self) -> anyhow::Result<Vec<InstanceInfo>> {
         anyhow::bail!("WSL API engine is not implemented yet")
     }
 
@@ -29,4 +30,44 @@ impl WslEngine for ApiEngine {
     fn create_from_distro(&self, _distro_name: &str, _name: &str) -> anyhow::Result<()> {
         anyhow::bail!("WSL API engine is not implemented yet")
     }
+
+    fn export(&self, _name: &str, _archive: &std::path::Path) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn terminate(&self, _name: &str) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn shutdown(&self) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn set_default(&self, _name: &str) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn set_version(&self, _name: &str, _version: u8) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn run_command(&self, _name: &str, _command: &[String]) -> anyhow::Result<CommandOutput> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn with_timeouts(&self, _overrides: &Timeouts) -> Box<dyn WslEngine> {
+        Box::new(ApiEngine::new())
+    }
+
+    fn check_available(&self) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn check_environment(&self, _dry_run: bool) -> anyhow::Result<()> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
+
+    fn is_known_distro(&self, _name: &str) -> anyhow::Result<bool> {
+        anyhow::bail!("WSL API engine is not implemented yet")
+    }
 }
@@ -1,78 +1,259 @@
-use crate::wsl::engine::WslEngine;
-use std::process::{Command, Stdio};
+use crate::config::Timeouts;
+use crate::wsl::engine::instances::{is_no_distributions_message, parse_list_verbose};
+use crate::wsl::engine::{CommandOutput, InstanceInfo, WslEngine};
+use crate::wsl::helpers::decode_wsl_output;
+use crate::wsl::provider::EngineOptions;
+use crate::wsl::runner::{run_once, run_with_retry, CommandRunner, Invocation, Operation, ProcessOutput};
+use crate::wsl::validation;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-pub struct CliEngine;
+#[derive(Clone)]
+pub struct CliEngine {
+    wsl_exe: PathBuf,
+    runner: Arc<dyn CommandRunner>,
+    timeouts: Timeouts,
+    inherit_output: bool,
+}
 
 impl CliEngine {
-    pub fn new() -> Self {
-        Self
+    pub fn new(options: &EngineOptions) -> Self {
+        Self {
+            wsl_exe: options.wsl_exe.clone(),
+            runner: options.runner.clone(),
+            timeouts: options.timeouts.clone(),
+            inherit_output: options.inherit_output,
+        }
     }
-}
 
-impl WslEngine for CliEngine {
-    fn instance_exists(&self, name: &str) -> anyhow::Result<bool> {
-        let status = Command::new("wsl.exe")
-            .args(["-d", name, "--", "echo", "Already exists."])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()?;
-        Ok(status.success())
+    fn output(&self, operation: Operation, invocation: Invocation) -> anyhow::Result<ProcessOutput> {
+        run_with_retry(&*self.runner, invocation, operation, &self.timeouts)
     }
 
-    fn delete_instance(&self, name: &str) -> anyhow::Result<()> {
-        let output = Command::new("wsl.exe").args(["--unregister", name]).output()?;
-
-        if !output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+    // Runs a wsl.exe management command, failing with its output when it exits non-zero.
+    fn run(&self, operation: Operation, args: &[&str]) -> anyhow::Result<()> {
+        let output = self.output(operation, Invocation::new(&self.wsl_exe).args(args))?;
+        if !output.success() {
             anyhow::bail!(
-                "wsl.exe --unregister failed with status {}\n{}\n{}",
-                output.status,
-                stdout.trim(),
-                stderr.trim()
+                "wsl.exe {} failed with {}\n{}\n{}",
+                args[0],
+                output.status(),
+                decode_wsl_output(&output.stdout).trim(),
+                decode_wsl_output(&output.stderr).trim()
             );
         }
         Ok(())
     }
 
-    fn create_from_file(
-        &self,
-        name: &str,
-        install_dir: &std::path::Path,
-        rootfs_tar: &std::path::Path,
-    ) -> anyhow::Result<()> {
-        let mut cmd = Command::new("wsl.exe");
-        cmd.args([
-            "--import",
-            name,
-            &install_dir.to_string_lossy(),
-            &rootfs_tar.to_string_lossy(),
-            "--version",
-            "2",
-        ]);
-
-        let output = cmd.output()?;
-        if !output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let stderr = String::from_utf8_lossy(&output.stderr);
+    // A path as wsl.exe on the Windows side understands it. When driving wsl.exe through
+    // interop from inside WSL, Linux paths are translated with `wslpath -w`. Both the
+    // lookup and the call go through the runner, so a transcript recorded under interop
+    // replays the same translations anywhere.
+    fn host_path(&self, path: &Path) -> anyhow::Result<String> {
+        if self.runner.check_program(Path::new("wslpath")).is_err() {
+            return Ok(path.to_string_lossy().into_owned());
+        }
+        let invocation = Invocation::new("wslpath").args(["-w", &path.to_string_lossy()]);
+        let output = run_once(&*self.runner, invocation, Operation::Other, &self.timeouts)?;
+        if !output.success() {
             anyhow::bail!(
-                "wsl.exe --import failed with status {}\n{}\n{}",
-                output.status,
-                stdout.trim(),
-                stderr.trim()
+                "wslpath -w {} failed: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
             );
         }
-        Ok(())
+        Ok(String::from_utf8_lossy(&output.stdout).trim_end().to_string())
     }
+}
 
-    fn create_from_distro(&self, distro_name: &str, name: &str) -> anyhow::Result<()> {
-        let mut cmd = Command::new("wsl.exe");
-        cmd.args(["--install", "-d", distro_name, "--name", name, "--no-launch"]);
+impl WslEngine for CliEngine {
+    fn list_instances(&self) -> anyhow::Result<Vec<InstanceInfo>> {
+        let output = self.output(
+            Operation::Other,
+            Invocation::new(&self.wsl_exe).args(["--list", "--verbose"]),
+        )?;
+        let stdout = decode_wsl_output(&output.stdout);
+        if !output.success() {
+            if is_no_distributions_message(&stdout) {
+                return Ok(Vec::new());
+            }
+            anyhow::bail!(
+                "wsl.exe --list --verbose failed with {}\n{}",
+                output.status(),
+                stdout.trim()
+            );
+        }
+        parse_list_verbose(&stdout)
+    }
+
+    fn delete_instance(&self, name: &str) -> anyhow::Result<()> {
+        self.run(Operation::Other, &["--unregister", name])
+    }
+
+    fn create_from_file(&self, name: &str, install_dir: &Path, rootfs_tar: &Path) -> anyhow::Result<()> {
+        self.run(
+            Operation::Import,
+            &[
+                "--import",
+                name,
+                &self.host_path(install_dir)?,
+                &self.host_path(rootfs_tar)?,
+                "--version",
+                "2",
+            ],
+        )
+    }
 
-        let status = cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit()).status()?;
-        if !status.success() {
-            anyhow::bail!("wsl.exe --install failed with status {}", status);
+    fn create_from_distro(&self, distro_name: &str, name: &str) -> anyhow::Result<()> {
+        let mut invocation =
+            Invocation::new(&self.wsl_exe).args(["--install", "-d", distro_name, "--name", name, "--no-launch"]);
+        if self.inherit_output {
+            invocation = invocation.inherit_output();
+        }
+        let output = self.output(Operation::Install, invocation)?;
+        if !output.success() {
+            anyhow::bail!(
+                "wsl.exe --install failed with {}\n{}",
+                output.status(),
+                decode_wsl_output(&output.stdout).trim()
+            );
         }
         Ok(())
     }
+
+    fn export(&self, name: &str, archive: &Path) -> anyhow::Result<()> {
+        self.run(Operation::Export, &["--export", name, &self.host_path(archive)?])
+    }
+
+    fn terminate(&self, name: &str) -> anyhow::Result<()> {
+        self.run(Operation::Other, &["--terminate", name])
+    }
+
+    fn shutdown(&self) -> anyhow::Result<()> {
+        self.run(Operation::Other, &["--shutdown"])
+    }
+
+    fn set_default(&self, name: &str) -> anyhow::Result<()> {
+        self.run(Operation::Other, &["--set-default", name])
+    }
+
+    fn set_version(&self, name: &str, version: u8) -> anyhow::Result<()> {
+        self.run(Operation::Other, &["--set-version", name, &version.to_string()])
+    }
+
+    fn run_command(&self, name: &str, command: &[String]) -> anyhow::Result<CommandOutput> {
+        let invocation = Invocation::new(&self.wsl_exe).args(["-d", name, "--"]).args(command);
+        // Never retried: the command may not be safe to run twice, and its output may well
+        // contain what looks like a transient wsl.exe error.
+        let output = run_once(&*self.runner, invocation, Operation::Command, &self.timeouts)?;
+        // This is the Linux command's own output, so UTF-8 rather than wsl.exe's UTF-16LE.
+        Ok(CommandOutput {
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            exit_code: output.exit_code,
+        })
+    }
+
+    fn check_environment(&self, dry_run: bool) -> anyhow::Result<()> {
+        validation::validate_environment(&*self.runner, &self.wsl_exe, &self.timeouts, dry_run)
+    }
+
+    fn is_known_distro(&self, name: &str) -> anyhow::Result<bool> {
+        validation::is_valid_wsl_distro_name(&*self.runner, &self.wsl_exe, &self.timeouts, name)
+    }
+
+    fn with_timeouts(&self, overrides: &Timeouts) -> Box<dyn WslEngine> {
+        Box::new(Self {
+            timeouts: self.timeouts.overridden_by(overrides),
+            ..self.clone()
+        })
+    }
+
+    fn check_available(&self) -> anyhow::Result<()> {
+        self.runner.check_program(&self.wsl_exe).with_context(|| {
+            "wsl.exe cannot run here; pass --wsl-exe (or set WSLFORGE_WSL_EXE / `wsl_exe`), or use --engine fake"
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wsl::engine::InstanceState;
+    use crate::wsl::runner::ReplayRunner;
+
+    fn replaying(transcript: &str) -> CliEngine {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/transcripts")
+            .join(transcript);
+        CliEngine::new(&EngineOptions {
+            runner: Arc::new(ReplayRunner::load(&path).unwrap()),
+            ..EngineOptions::default()
+        })
+    }
+
+    #[test]
+    fn replays_list_import_and_unregister() {
+        let engine = replaying("import-unregister.json");
+
+        assert!(!engine.instance_exists("dev").unwrap());
+        engine
+            .create_from_file("dev", Path::new("C:\\VMs\\dev"), Path::new("C:\\images\\rootfs.tar"))
+            .unwrap();
+        let instances = engine.list_instances().unwrap();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(instances[1].name, "dev");
+        assert_eq!(instances[1].state, InstanceState::Stopped);
+        assert!(instances[0].is_default);
+        engine.delete_instance("dev").unwrap();
+        engine.delete_instance("Ubuntu").unwrap();
+        // wsl.exe exits non-zero with a message once the last distro is gone.
+        assert!(engine.list_instances().unwrap().is_empty());
+    }
+
+    #[test]
+    fn replays_the_wslpath_translations_of_a_run_under_interop() {
+        // Recorded inside WSL; replays the same on a machine without `wslpath`.
+        let engine = replaying("import-interop.json");
+
+        engine
+            .create_from_file(
+                "dev",
+                Path::new("/home/me/vms/dev"),
+                Path::new("/mnt/c/images/rootfs.tar"),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn a_command_the_transcript_does_not_expect_fails_the_operation() {
+        let engine = replaying("import-unregister.json");
+
+        engine.list_instances().unwrap();
+        let err = engine.delete_instance("dev").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("expected `C:\\Windows\\System32\\wsl.exe --import dev"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn retries_an_install_that_hit_a_busy_service() {
+        // Output is captured even when it is shown on the terminal, as it is by default.
+        let engine = replaying("install-busy.json");
+
+        engine.create_from_distro("Ubuntu-24.04", "dev").unwrap();
+    }
+
+    #[test]
+    fn runs_user_commands_once_even_when_they_print_a_transient_error() {
+        let engine = replaying("run-command-busy.json");
+
+        // A retry would ask the transcript for a second run it does not have.
+        let output = engine.run_command("dev", &["./provision.sh".to_string()]).unwrap();
+        assert_eq!(output.exit_code, Some(1));
+        assert!(output.stdout.contains("the service is busy"));
+    }
 }
@@ -0,0 +1,271 @@
+// A stand-in for WSL that keeps its instances in a JSON state file, so the whole apply /
+// plan / status / destroy flow can run on Linux CI or in a demo without a Windows box.
+// - The state file is `EngineOptions::fake_state`, `WSLFORGE_FAKE_STATE`, or
+//   `wslforge-fake-wsl.json` in the temp dir. Each call loads and saves it, so separate
+//   runs see each other's instances.
+// - Import creates the install dir and checks the rootfs exists; install only accepts the
+//   distros listed in `ONLINE_DISTROS`. Names compare case-insensitively, as in WSL.
+// - An empty rootfs fails the import after registering the instance, the way a corrupt
+//   archive can leave a half-registered instance behind, so rollback can be exercised.
+// - `run_command` understands `echo`, `true` and `false`; anything else exits with 127.
+use crate::config::Timeouts;
+use crate::wsl::engine::{CommandOutput, InstanceInfo, InstanceState, WslEngine};
+use crate::wsl::provider::EngineOptions;
+use anyhow::Context;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub const STATE_ENV: &str = "WSLFORGE_FAKE_STATE";
+
+// A typical `wsl --list --online` answer.
+const ONLINE_DISTROS: &[&str] = &[
+    "Ubuntu",
+    "Ubuntu-22.04",
+    "Ubuntu-24.04",
+    "Debian",
+    "kali-linux",
+    "openSUSE-Tumbleweed",
+    "AlmaLinux-9",
+    "FedoraLinux-42",
+    "archlinux",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FakeState {
+    instances: Vec<FakeInstance>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FakeInstance {
+    name: String,
+    running: bool,
+    version: u8,
+    default: bool,
+    source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    install_dir: Option<PathBuf>,
+}
+
+#[derive(Clone)]
+pub struct FakeEngine {
+    state_file: PathBuf,
+    // Serializes load-modify-save cycles within this process.
+    lock: Arc<Mutex<()>>,
+}
+
+impl FakeEngine {
+    pub fn new(options: &EngineOptions) -> Self {
+        let state_file = options
+            .fake_state
+            .clone()
+            .or_else(|| std::env::var_os(STATE_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| std::env::temp_dir().join("wslforge-fake-wsl.json"));
+        info!("🧪 Using the fake WSL engine, state in {}", state_file.display());
+        Self {
+            state_file,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn load(&self) -> anyhow::Result<FakeState> {
+        match std::fs::read_to_string(&self.state_file) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("invalid fake WSL state file: {}", self.state_file.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(FakeState::default()),
+            Err(err) => {
+                Err(err).with_context(|| format!("unable to read fake WSL state file: {}", self.state_file.display()))
+            }
+        }
+    }
+
+    fn save(&self, state: &FakeState) -> anyhow::Result<()> {
+        std::fs::write(&self.state_file, serde_json::to_string_pretty(state)?)
+            .with_context(|| format!("unable to write fake WSL state file: {}", self.state_file.display()))
+    }
+
+    fn update<T>(&self, change: impl FnOnce(&mut FakeState) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = self.load()?;
+        let result = change(&mut state)?;
+        self.save(&state)?;
+        Ok(result)
+    }
+}
+
+impl FakeState {
+    fn position(&self, name: &str) -> Option<usize> {
+        self.instances
+            .iter()
+            .position(|instance| instance.name.eq_ignore_ascii_case(name))
+    }
+
+    fn index(&self, name: &str) -> anyhow::Result<usize> {
+        match self.position(name) {
+            Some(index) => Ok(index),
+            None => anyhow::bail!("There is no distribution with the supplied name: {}", name),
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> anyhow::Result<&mut FakeInstance> {
+        let index = self.index(name)?;
+        Ok(&mut self.instances[index])
+    }
+
+    fn add(&mut self, name: &str, source: String, install_dir: Option<PathBuf>) -> anyhow::Result<()> {
+        if self.position(name).is_some() {
+            anyhow::bail!("A distribution with the supplied name already exists: {}", name);
+        }
+        let default = self.instances.is_empty();
+        self.instances.push(FakeInstance {
+            name: name.to_string(),
+            running: false,
+            version: 2,
+            default,
+            source,
+            install_dir,
+        });
+        Ok(())
+    }
+}
+
+impl WslEngine for FakeEngine {
+    fn list_instances(&self) -> anyhow::Result<Vec<InstanceInfo>> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(self
+            .load()?
+            .instances
+            .into_iter()
+            .map(|instance| InstanceInfo {
+                name: instance.name,
+                state: if instance.running {
+                    InstanceState::Running
+                } else {
+                    InstanceState::Stopped
+                },
+                version: instance.version,
+                is_default: instance.default,
+            })
+            .collect())
+    }
+
+    fn delete_instance(&self, name: &str) -> anyhow::Result<()> {
+        self.update(|state| {
+            let index = state.index(name)?;
+            let removed = state.instances.remove(index);
+            if removed.default {
+                if let Some(first) = state.instances.first_mut() {
+                    first.default = true;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn create_from_file(&self, name: &str, install_dir: &Path, rootfs_tar: &Path) -> anyhow::Result<()> {
+        if !rootfs_tar.is_file() {
+            anyhow::bail!("The system cannot find the file specified: {}", rootfs_tar.display());
+        }
+        self.update(|state| {
+            state.add(
+                name,
+                format!("file:{}", rootfs_tar.display()),
+                Some(install_dir.to_path_buf()),
+            )?;
+            std::fs::create_dir_all(install_dir)
+                .with_context(|| format!("unable to create install dir: {}", install_dir.display()))
+        })?;
+        if std::fs::metadata(rootfs_tar)?.len() == 0 {
+            anyhow::bail!("The archive is empty or corrupt: {}", rootfs_tar.display());
+        }
+        Ok(())
+    }
+
+    fn create_from_distro(&self, distro_name: &str, name: &str) -> anyhow::Result<()> {
+        if !self.is_known_distro(distro_name)? {
+            anyhow::bail!("Invalid distribution name: '{}'", distro_name);
+        }
+        self.update(|state| state.add(name, format!("distro:{}", distro_name), None))
+    }
+
+    fn export(&self, name: &str, archive: &Path) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let state = self.load()?;
+        let instance = &state.instances[state.index(name)?];
+        // Any file will do as an archive: `create_from_file` only checks that it exists.
+        std::fs::write(archive, serde_json::to_string_pretty(instance)?)
+            .with_context(|| format!("unable to write export archive: {}", archive.display()))
+    }
+
+    fn terminate(&self, name: &str) -> anyhow::Result<()> {
+        self.update(|state| {
+            state.get_mut(name)?.running = false;
+            Ok(())
+        })
+    }
+
+    fn shutdown(&self) -> anyhow::Result<()> {
+        self.update(|state| {
+            state.instances.iter_mut().for_each(|instance| instance.running = false);
+            Ok(())
+        })
+    }
+
+    fn set_default(&self, name: &str) -> anyhow::Result<()> {
+        self.update(|state| {
+            state.index(name)?;
+            for instance in &mut state.instances {
+                instance.default = instance.name.eq_ignore_ascii_case(name);
+            }
+            Ok(())
+        })
+    }
+
+    fn set_version(&self, name: &str, version: u8) -> anyhow::Result<()> {
+        if !matches!(version, 1 | 2) {
+            anyhow::bail!("Invalid WSL version: {}", version);
+        }
+        self.update(|state| {
+            state.get_mut(name)?.version = version;
+            Ok(())
+        })
+    }
+
+    fn run_command(&self, name: &str, command: &[String]) -> anyhow::Result<CommandOutput> {
+        self.update(|state| {
+            state.get_mut(name)?.running = true;
+            Ok(())
+        })?;
+        let output = match command.split_first() {
+            Some((program, args)) if program == "echo" => (format!("{}\n", args.join(" ")), String::new(), 0),
+            Some((program, _)) if program == "true" => (String::new(), String::new(), 0),
+            Some((program, _)) if program == "false" => (String::new(), String::new(), 1),
+            Some((program, _)) => (String::new(), format!("{}: command not found\n", program), 127),
+            None => (String::new(), String::new(), 0),
+        };
+        Ok(CommandOutput {
+            stdout: output.0,
+            stderr: output.1,
+            exit_code: Some(output.2),
+        })
+    }
+
+    // Nothing here can hang, so there is nothing to time out.
+    fn with_timeouts(&self, _overrides: &Timeouts) -> Box<dyn WslEngine> {
+        Box::new(self.clone())
+    }
+
+    fn check_available(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn check_environment(&self, _dry_run: bool) -> anyhow::Result<()> {
+        info!("🧪 Fake engine: skipping WSL and Windows feature checks");
+        Ok(())
+    }
+
+    fn is_known_distro(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(ONLINE_DISTROS.iter().any(|known| known.eq_ignore_ascii_case(name)))
+    }
+}
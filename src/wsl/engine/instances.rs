@@ -0,0 +1,252 @@
+// Parsing of `wsl --list --verbose`, which wsl.exe writes as UTF-16LE:
+//
+//     NAME            STATE           VERSION
+//   * Ubuntu          Running         2
+//     Debian          Stopped         1
+//
+// The header is localized, so its words are never matched; only where they start is used.
+// wsl.exe pads every column to the header, so those positions split each row even when a
+// name or a localized state contains spaces. A header that is not three words falls back to
+// splitting on whitespace, with the first word as the name.
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstanceState {
+    Running,
+    Stopped,
+    Installing,
+    Converting,
+    Uninstalling,
+    // Anything else wsl.exe may report, e.g. a localized state name.
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceInfo {
+    pub name: String,
+    pub state: InstanceState,
+    pub version: u8,
+    pub is_default: bool,
+}
+
+impl fmt::Display for InstanceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceState::Running => write!(f, "running"),
+            InstanceState::Stopped => write!(f, "stopped"),
+            InstanceState::Installing => write!(f, "installing"),
+            InstanceState::Converting => write!(f, "converting"),
+            InstanceState::Uninstalling => write!(f, "uninstalling"),
+            InstanceState::Other(state) => write!(f, "{}", state.to_lowercase()),
+        }
+    }
+}
+
+impl InstanceState {
+    fn parse(state: &str) -> Self {
+        match state {
+            "Running" => InstanceState::Running,
+            "Stopped" => InstanceState::Stopped,
+            "Installing" => InstanceState::Installing,
+            "Converting" => InstanceState::Converting,
+            "Uninstalling" => InstanceState::Uninstalling,
+            other => InstanceState::Other(other.to_string()),
+        }
+    }
+}
+
+pub(crate) fn parse_list_verbose(text: &str) -> anyhow::Result<Vec<InstanceInfo>> {
+    let mut lines = text
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim_end())
+        .filter(|line| !line.is_empty());
+    let Some(header) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let columns = Columns::from_header(header);
+    lines.map(|line| parse_line(line, columns)).collect()
+}
+
+// With no distro installed, wsl.exe prints a message instead of the table and exits
+// non-zero. The message is localized, but the command or store link it points to is not.
+pub(crate) fn is_no_distributions_message(text: &str) -> bool {
+    ["--install", "aka.ms/wslstore"]
+        .iter()
+        .any(|marker| text.contains(marker))
+}
+
+// Character offsets where the STATE and VERSION columns start.
+#[derive(Debug, Clone, Copy)]
+struct Columns {
+    state: usize,
+    version: usize,
+}
+
+impl Columns {
+    fn from_header(header: &str) -> Option<Self> {
+        let chars: Vec<char> = header.chars().collect();
+        let starts: Vec<usize> = (0..chars.len())
+            .filter(|&i| !chars[i].is_whitespace() && (i == 0 || chars[i - 1].is_whitespace()))
+            .collect();
+        match starts[..] {
+            [_, state, version] => Some(Self { state, version }),
+            _ => None,
+        }
+    }
+
+    // Splits a row at the header's offsets, if it lines up with them.
+    fn split(self, line: &str) -> Option<(String, String, String)> {
+        let chars: Vec<char> = line.chars().collect();
+        let starts_column = |i: usize| chars.get(i).is_some_and(|c| !c.is_whitespace()) && chars[i - 1].is_whitespace();
+        if !starts_column(self.state) || !starts_column(self.version) {
+            return None;
+        }
+        let field = |range: std::ops::Range<usize>| chars[range].iter().collect::<String>().trim().to_string();
+        let (name, state, version) = (
+            field(0..self.state),
+            field(self.state..self.version),
+            field(self.version..chars.len()),
+        );
+        (!name.is_empty() && !version.contains(char::is_whitespace)).then_some((name, state, version))
+    }
+}
+
+fn parse_line(line: &str, columns: Option<Columns>) -> anyhow::Result<InstanceInfo> {
+    // Blank out the default marker in place so the columns still line up.
+    let is_default = line.trim_start().starts_with('*');
+    let unmarked = match is_default {
+        true => line.replacen('*', " ", 1),
+        false => line.to_string(),
+    };
+    let (name, state, version) = columns
+        .and_then(|columns| columns.split(&unmarked))
+        .or_else(|| split_on_whitespace(&unmarked))
+        .ok_or_else(|| anyhow::anyhow!("unexpected line in `wsl --list --verbose` output: '{}'", line.trim()))?;
+    let version = version
+        .parse()
+        .map_err(|_| anyhow::anyhow!("unexpected WSL version '{}' for instance '{}'", version, name))?;
+    Ok(InstanceInfo {
+        name,
+        state: InstanceState::parse(&state),
+        version,
+        is_default,
+    })
+}
+
+// A localized state may contain spaces, so it is whatever sits between name and version.
+fn split_on_whitespace(line: &str) -> Option<(String, String, String)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    match &fields[..] {
+        [name, state @ .., version] if !state.is_empty() => {
+            Some((name.to_string(), state.join(" "), version.to_string()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wsl::helpers::decode_wsl_output;
+
+    // Captures of `wsl --list --verbose` as wsl.exe writes them: UTF-16LE with CRLF.
+    macro_rules! fixture {
+        ($name:literal) => {
+            decode_wsl_output(include_bytes!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/list-verbose/",
+                $name
+            )))
+        };
+    }
+
+    fn instance(name: &str, state: InstanceState, version: u8, is_default: bool) -> InstanceInfo {
+        InstanceInfo {
+            name: name.into(),
+            state,
+            version,
+            is_default,
+        }
+    }
+
+    #[test]
+    fn parses_the_default_marker_and_versions() {
+        assert_eq!(
+            parse_list_verbose(&fixture!("default.txt")).unwrap(),
+            [
+                instance("Ubuntu-22.04", InstanceState::Running, 2, true),
+                instance("docker-desktop", InstanceState::Stopped, 2, false),
+                instance("Debian", InstanceState::Stopped, 1, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_localized_multi_word_states() {
+        let other = |state: &str| InstanceState::Other(state.into());
+        assert_eq!(
+            parse_list_verbose(&fixture!("german.txt")).unwrap(),
+            [
+                instance("Ubuntu", other("Wird ausgeführt"), 2, true),
+                instance("Debian", other("Beendet"), 2, false),
+                instance("Alpine", other("Wird installiert"), 2, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_names_with_spaces_at_the_header_columns() {
+        assert_eq!(
+            parse_list_verbose(&fixture!("names-with-spaces.txt")).unwrap(),
+            [
+                instance("My Distro", InstanceState::Stopped, 2, false),
+                instance("Other Box Two", InstanceState::Running, 2, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_a_bom_and_trailing_crlf() {
+        let text = fixture!("bom-crlf.txt");
+        assert!(text.ends_with("\r\n\r\n"));
+        let expected = [instance("dev", InstanceState::Stopped, 2, true)];
+        assert_eq!(parse_list_verbose(&text).unwrap(), expected);
+        // The UTF-16 decoder drops the BOM; UTF-8 output (`WSL_UTF8=1`) keeps it in the text.
+        assert_eq!(parse_list_verbose(&format!("\u{feff}{text}")).unwrap(), expected);
+    }
+
+    #[test]
+    fn recognizes_the_no_distributions_message() {
+        for text in [fixture!("no-distributions.txt"), fixture!("no-distributions-store.txt")] {
+            assert!(is_no_distributions_message(&text), "{text}");
+        }
+        assert!(!is_no_distributions_message(&fixture!("default.txt")));
+    }
+
+    #[test]
+    fn falls_back_to_whitespace_without_a_three_word_header() {
+        let text = "  NOM DU SYSTÈME   ÉTAT        VERSION\r\n* Ubuntu          En cours    2\r\n";
+        assert_eq!(
+            parse_list_verbose(text).unwrap(),
+            [instance("Ubuntu", InstanceState::Other("En cours".into()), 2, true)]
+        );
+    }
+
+    #[test]
+    fn empty_output_is_an_empty_list() {
+        assert_eq!(parse_list_verbose("").unwrap(), []);
+        assert_eq!(parse_list_verbose("  NAME  STATE  VERSION\r\n").unwrap(), []);
+    }
+
+    #[test]
+    fn rejects_rows_that_do_not_parse() {
+        let header = "  NAME      STATE           VERSION\r\n";
+        let err = parse_list_verbose(&format!("{header}  broken\r\n")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected line in `wsl --list --verbose` output: 'broken'"
+        );
+        let err = parse_list_verbose(&format!("{header}  dev       Stopped         two\r\n")).unwrap_err();
+        assert_eq!(err.to_string(), "unexpected WSL version 'two' for instance 'dev'");
+    }
+}
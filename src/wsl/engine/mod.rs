@@ -1,15 +1,49 @@
+use crate::config::Timeouts;
+
 pub mod api;
 pub mod cli;
+pub mod fake;
+pub mod instances;
+
+pub use instances::{InstanceInfo, InstanceState};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CreateOutcome {
     Created,
+    Replaced,
     AlreadyExists,
-    Skipped,
+    // Dry run: the instance would have been created or replaced.
+    Planned,
+    Failed,
 }
 
-pub trait WslEngine {
-    fn instance_exists(&self, name: &str) -> anyhow::Result<bool>;
+// What a command run inside an instance produced. `exit_code` is `None` when the process
+// was killed by a signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+}
+
+impl CommandOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+// `Send + Sync` so `apply --jobs` can share one engine across threads.
+pub trait WslEngine: Send + Sync {
+    fn list_instances(&self) -> anyhow::Result<Vec<InstanceInfo>>;
+
+    // WSL compares instance names case-insensitively.
+    fn instance_exists(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .list_instances()?
+            .iter()
+            .any(|instance| instance.name.eq_ignore_ascii_case(name)))
+    }
+
     fn delete_instance(&self, name: &str) -> anyhow::Result<()>;
     fn create_from_file(
         &self,
@@ -18,4 +52,19 @@ pub trait WslEngine {
         rootfs_tar: &std::path::Path,
     ) -> anyhow::Result<()>;
     fn create_from_distro(&self, distro_name: &str, name: &str) -> anyhow::Result<()>;
+    fn export(&self, name: &str, archive: &std::path::Path) -> anyhow::Result<()>;
+    fn terminate(&self, name: &str) -> anyhow::Result<()>;
+    fn shutdown(&self) -> anyhow::Result<()>;
+    fn set_default(&self, name: &str) -> anyhow::Result<()>;
+    fn set_version(&self, name: &str, version: u8) -> anyhow::Result<()>;
+    fn run_command(&self, name: &str, command: &[String]) -> anyhow::Result<CommandOutput>;
+
+    // The same engine, with `overrides` (e.g. a profile's `timeouts`) over its timeouts.
+    fn with_timeouts(&self, overrides: &Timeouts) -> Box<dyn WslEngine>;
+    // Whether this engine can run here at all.
+    fn check_available(&self) -> anyhow::Result<()>;
+    // Host checks before anything is created: WSL installed and up to date, Windows features.
+    fn check_environment(&self, dry_run: bool) -> anyhow::Result<()>;
+    // Whether `wsl --install -d <name>` would find the distro.
+    fn is_known_distro(&self, name: &str) -> anyhow::Result<bool>;
 }
@@ -0,0 +1,87 @@
+// A digest of everything that decides what an instance looks like when it is created: the
+// resolved profile, its rendered cloud-init and the image. Settings that only change how
+// wslforge gets there (`override`, backups, timeouts) are left out, so editing them does
+// not count as a change. Nothing is read from a password source: cloud-init is rendered
+// with the password's declaration instead (see `PasswordSource::declaration`).
+// An image file is identified by its size and modification time rather than its content:
+// hashing a multi-gigabyte rootfs on every `plan` would be far too slow.
+use crate::config::{ImageSource, OverridePolicy};
+use crate::wsl::cloud_init;
+use crate::wsl::resolve::ResolvedProfile;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+pub(crate) fn fingerprint(profile: &ResolvedProfile) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    let shape = ResolvedProfile {
+        override_instance: OverridePolicy::Never,
+        ..profile.clone()
+    };
+    hasher.update(serde_json::to_vec(&shape)?);
+    if let Some(rendered) = cloud_init::render_profile_cloud_init_stable(profile)? {
+        hasher.update([0]);
+        hasher.update(rendered);
+    }
+    if let ImageSource::File { path } = &profile.image {
+        hasher.update([0]);
+        hasher.update(image_file_identity(path));
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+// A missing image just reads as different; creating from it reports the actual problem.
+fn image_file_identity(path: &Path) -> String {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return "missing".into();
+    };
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_nanos())
+        .unwrap_or_default();
+    format!("{}:{}", metadata.len(), modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+
+    fn fingerprint_of(extra: &str) -> String {
+        let yaml = format!(
+            "hostname: dev\ninstall_dir: /vms\ncloud_init:\n  type: inline\n  content: \"hash: {{{{ password_hash }}}}\"\n{extra}"
+        );
+        let profile: Profile = serde_yaml::from_str(&yaml).unwrap();
+        fingerprint(&ResolvedProfile::resolve("dev", &profile).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn never_runs_the_password_command() {
+        // This command cannot run; a fingerprint that read the password would fail.
+        let password = "password: { command: [/nonexistent/token-helper, --fresh] }\n";
+        assert_eq!(fingerprint_of(password), fingerprint_of(password));
+    }
+
+    #[test]
+    fn changes_with_the_password_declaration() {
+        let base = fingerprint_of("password: { env: DEV_PASSWORD }\n");
+        assert_ne!(base, fingerprint_of("password: { env: OTHER_PASSWORD }\n"));
+        assert_ne!(base, fingerprint_of("password: { file: /secrets/dev }\n"));
+        assert_ne!(fingerprint_of("password: one\n"), fingerprint_of("password: two\n"));
+        assert_ne!(base, fingerprint_of(""));
+    }
+
+    #[test]
+    fn ignores_how_wslforge_gets_there() {
+        let base = fingerprint_of("");
+        assert_eq!(base, fingerprint_of("override: always\n"));
+        assert_eq!(base, fingerprint_of("timeouts: { import: 2h }\n"));
+        assert_eq!(
+            base,
+            fingerprint_of("backup_before_override: true\nbackup_retention: 9\n")
+        );
+        assert_ne!(base, fingerprint_of("username: other\n"));
+    }
+}
@@ -1,19 +1,28 @@
+use encoding_rs::{Encoding, UTF_16LE, UTF_8};
 use sha_crypt::{sha512_simple, Sha512Params, ROUNDS_DEFAULT};
+use std::path::{Path, PathBuf};
 
-// Expands env vars, supporting both %VAR% and $VAR styles.
+// Expands env vars, supporting both %VAR% and $VAR styles, plus a leading `~`.
 pub(crate) fn expand_env_vars(raw: &str) -> anyhow::Result<String> {
     let percent_expanded = expand_str::expand_string_with_env(raw)
         .map_err(|e| anyhow::anyhow!("environment variable expansion failed: {e}"))?;
-    let expanded = shellexpand::env(&percent_expanded)
+    let expanded = shellexpand::full(&percent_expanded)
         .map_err(|e| anyhow::anyhow!("environment variable '{}' is not set (from '{}')", e.var_name, raw))?;
     Ok(expanded.into_owned())
 }
 
-pub(crate) fn resolve_userprofile_dir() -> anyhow::Result<std::path::PathBuf> {
+// The current UTC time in RFC 3339, to the second.
+pub(crate) fn timestamp() -> String {
+    humantime::format_rfc3339_seconds(std::time::SystemTime::now()).to_string()
+}
+
+pub(crate) fn resolve_userprofile_dir() -> anyhow::Result<PathBuf> {
     if let Some(path) = std::env::var_os("USERPROFILE") {
-        return Ok(std::path::PathBuf::from(path));
+        return Ok(PathBuf::from(path));
     }
-    anyhow::bail!("USERPROFILE is not set; cannot place cloud-init user-data")
+    anyhow::bail!(
+        "USERPROFILE is not set; cannot place cloud-init user-data (inside WSL, share it from Windows with WSLENV=USERPROFILE/p)"
+    )
 }
 
 pub(crate) fn hash_password_sha512(password: &str) -> anyhow::Result<String> {
@@ -21,3 +30,49 @@ pub(crate) fn hash_password_sha512(password: &str) -> anyhow::Result<String> {
         Sha512Params::new(ROUNDS_DEFAULT).map_err(|e| anyhow::anyhow!("invalid sha512-crypt params: {e:?}"))?;
     sha512_simple(password, &params).map_err(|e| anyhow::anyhow!("password hashing failed: {e:?}"))
 }
+
+// wsl.exe writes its own messages as UTF-16LE, unless `WSL_UTF8=1` is set in which case
+// they are UTF-8. UTF-16LE ASCII text has a NUL in every second byte, which tells them apart.
+pub(crate) fn decode_wsl_output(bytes: &[u8]) -> String {
+    if wsl_output_encoding(bytes) == UTF_16LE {
+        let (text, _, _) = UTF_16LE.decode(bytes);
+        text.into_owned()
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+// Which of the two the output starting with `bytes` is; needs its first two bytes.
+pub(crate) fn wsl_output_encoding(bytes: &[u8]) -> &'static Encoding {
+    match bytes.starts_with(&[0xFF, 0xFE]) || bytes.get(1) == Some(&0) {
+        true => UTF_16LE,
+        false => UTF_8,
+    }
+}
+
+// Inside a WSL distro with interop enabled, Windows executables such as wsl.exe can be run
+// directly; the kernel registers this binfmt handler for them.
+pub(crate) fn wsl_interop_enabled() -> bool {
+    [
+        "/proc/sys/fs/binfmt_misc/WSLInterop",
+        "/proc/sys/fs/binfmt_misc/WSLInterop-late",
+    ]
+    .iter()
+    .any(|handler| Path::new(handler).exists())
+}
+
+// Finds an executable given as a path or as a bare name on PATH (trying `.exe` on Windows).
+pub(crate) fn find_executable(program: &Path) -> Option<PathBuf> {
+    if program.components().count() > 1 {
+        return program.is_file().then(|| program.to_path_buf());
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).find_map(|dir| {
+        let candidate = dir.join(program);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        let with_exe = candidate.with_extension("exe");
+        (cfg!(windows) && with_exe.is_file()).then_some(with_exe)
+    })
+}
@@ -1,86 +1,639 @@
-use crate::config::{ImageSource, Profile};
-use crate::wsl::engine::CreateOutcome;
-use crate::wsl::{cloud_init, provider, reporting, validation};
-use log::info;
+use crate::config::{ImageSource, Profile, Timeouts};
+use crate::wsl::apply::{ApplyReport, ApplyResult};
+use crate::wsl::engine::{CommandOutput, CreateOutcome, InstanceInfo};
+use crate::wsl::helpers::timestamp;
+use crate::wsl::plan::{self, Drift, PlanAction, PlanEntry};
+use crate::wsl::resolve::ResolvedProfile;
+use crate::wsl::rollback::Rollback;
+use crate::wsl::snapshots::{Snapshot, SnapshotDir, SnapshotKind};
+use crate::wsl::state::{ManagedInstance, StateStore};
+use crate::wsl::{cloud_init, fingerprint, provider, reporting, runner, validation};
+use anyhow::Context;
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// What `destroy_instance` removed, or would have removed in a dry run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Destroyed {
+    pub hostname: String,
+    pub unregistered: bool,
+    pub removed: Vec<PathBuf>,
+}
 
 pub struct WslManager {
     provider: provider::WslProvider,
     dry_run: bool,
     debug: bool,
+    rollback: bool,
+    backup_before_override: bool,
+    state: Option<StateStore>,
 }
 
 impl WslManager {
     pub fn new(dry_run: bool, debug: bool) -> Self {
         Self {
-            provider: provider::WslProvider::new(provider::EngineKind::Cli),
+            provider: provider::WslProvider::new(provider::EngineKind::Cli, &provider::EngineOptions::default()),
             dry_run,
             debug,
+            rollback: true,
+            backup_before_override: false,
+            state: None,
         }
     }
 
-    pub fn with_engine(kind: provider::EngineKind, dry_run: bool, debug: bool) -> Self {
+    pub fn with_engine(
+        kind: provider::EngineKind,
+        options: &provider::EngineOptions,
+        dry_run: bool,
+        debug: bool,
+    ) -> Self {
         Self {
-            provider: provider::WslProvider::new(kind),
+            provider: provider::WslProvider::new(kind, options),
             dry_run,
             debug,
+            rollback: true,
+            backup_before_override: false,
+            state: None,
         }
     }
 
+    // With rollback off, a failed create leaves whatever it got to for inspection.
+    pub fn with_rollback(mut self, rollback: bool) -> Self {
+        self.rollback = rollback;
+        self
+    }
+
+    // Back up every instance before overriding it, whatever its profile says.
+    pub fn with_backups(mut self, backup_before_override: bool) -> Self {
+        self.backup_before_override = backup_before_override;
+        self
+    }
+
+    // Without a state store nothing is recorded, and every instance counts as unmanaged.
+    pub fn with_state(mut self, state: StateStore) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn ensure_available(&self) -> anyhow::Result<()> {
+        self.provider.check_available()
+    }
+
     pub fn validate_environment(&self) -> anyhow::Result<()> {
-        validation::validate_environment(self.dry_run)
+        self.provider.check_environment(self.dry_run)
+    }
+
+    // Two profiles must not end up as the same WSL instance. Profiles that fail to resolve
+    // are skipped here and reported when they are planned or created.
+    pub fn validate_profiles(&self, profiles: &BTreeMap<String, Profile>) -> anyhow::Result<()> {
+        let resolved: Vec<ResolvedProfile> = profiles
+            .iter()
+            .filter_map(|(name, profile)| ResolvedProfile::resolve(name, profile).ok())
+            .collect();
+        validation::validate_unique_names(&resolved)
+    }
+
+    pub fn plan_instance(&self, profile_name: &str, profile: &Profile) -> PlanEntry {
+        let resolved = ResolvedProfile::resolve(profile_name, profile);
+        let hostname = match &resolved {
+            Ok(resolved) => resolved.hostname.clone(),
+            Err(_) => profile.hostname.clone(),
+        };
+        let (action, reason) = match resolved.and_then(|resolved| self.plan_profile(&resolved)) {
+            Ok(decision) => decision,
+            Err(err) => (PlanAction::Error, format!("{err:#}")),
+        };
+        PlanEntry {
+            profile: profile_name.to_string(),
+            hostname,
+            action,
+            reason,
+        }
+    }
+
+    // Applies `profiles` on up to `jobs` threads, starting them in config order; the report
+    // lists them in that order too. Unless `keep_going`, a failure (and Ctrl-C in any case)
+    // stops further profiles from starting, while those already running finish.
+    pub fn apply_profiles(&self, profiles: &BTreeMap<String, Profile>, jobs: usize, keep_going: bool) -> ApplyReport {
+        let queue = Mutex::new(profiles.iter().enumerate());
+        let results = Mutex::new(Vec::new());
+        let stop = AtomicBool::new(false);
+        let worker = || loop {
+            if stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let Some((index, (profile_name, profile))) = queue.lock().unwrap_or_else(|e| e.into_inner()).next() else {
+                break;
+            };
+            let result = match jobs {
+                1 => self.apply_instance(profile_name, profile),
+                _ => reporting::with_log_prefix(profile_name, || self.apply_instance(profile_name, profile)),
+            };
+            if result
+                .error
+                .as_ref()
+                .is_some_and(|err| !keep_going || err.is::<runner::Interrupted>())
+            {
+                stop.store(true, Ordering::SeqCst);
+            }
+            results.lock().unwrap_or_else(|e| e.into_inner()).push((index, result));
+        };
+        std::thread::scope(|scope| {
+            for _ in 0..jobs.min(profiles.len()) {
+                scope.spawn(worker);
+            }
+        });
+
+        let mut results = results.into_inner().unwrap_or_else(|e| e.into_inner());
+        results.sort_by_key(|(index, _)| *index);
+        ApplyReport {
+            results: results.into_iter().map(|(_, result)| result).collect(),
+        }
+    }
+
+    // Runs `create_instance` for `apply`, timing it and turning a failure into a result so
+    // the caller decides whether to carry on with the next profile.
+    pub fn apply_instance(&self, profile_name: &str, profile: &Profile) -> ApplyResult {
+        let started = Instant::now();
+        let hostname = match ResolvedProfile::resolve(profile_name, profile) {
+            Ok(resolved) => resolved.hostname,
+            Err(_) => profile.hostname.clone(),
+        };
+        let (outcome, error) = match self.create_instance(profile_name, profile) {
+            Ok(outcome) => (outcome, None),
+            Err(err) => {
+                reporting::log_create_outcome(CreateOutcome::Failed, &hostname);
+                (CreateOutcome::Failed, Some(err))
+            }
+        };
+        ApplyResult {
+            profile: profile_name.to_string(),
+            hostname,
+            outcome,
+            duration: started.elapsed(),
+            error,
+        }
     }
 
-    pub fn create_instance(&self, profile_name: &str, profile: &Profile) -> anyhow::Result<()> {
-        let instance_exists = self.provider.instance_exists(&profile.hostname)?;
-        if profile.override_instance {
-            self.delete_instance(&profile.hostname, instance_exists)?;
-        } else if instance_exists {
-            reporting::log_create_outcome(CreateOutcome::AlreadyExists, &profile.hostname);
+    pub fn create_instance(&self, profile_name: &str, profile: &Profile) -> anyhow::Result<CreateOutcome> {
+        let profile = &ResolvedProfile::resolve(profile_name, profile)?;
+        let provider = &self.provider_for(profile);
+        validation::validate_profile_names(profile)?;
+        let instance_exists = provider.instance_exists(&profile.hostname)?;
+        let replace = match plan::decide(profile, instance_exists, || self.drift(profile))? {
+            (PlanAction::Skip, _) => {
+                reporting::log_create_outcome(CreateOutcome::AlreadyExists, &profile.hostname);
+                return Ok(CreateOutcome::AlreadyExists);
+            }
+            (PlanAction::Replace, _) => {
+                if profile.backup_before_override || self.backup_before_override {
+                    self.back_up_instance(provider, profile)?;
+                }
+                self.delete_instance(provider, &profile.hostname)?;
+                true
+            }
+            _ => false,
+        };
+
+        let mut rollback = Rollback::default();
+        match self.provision(provider, profile, &mut rollback) {
+            Ok(CreateOutcome::Created) if replace => {
+                reporting::log_create_outcome(CreateOutcome::Replaced, &profile.hostname);
+                Ok(CreateOutcome::Replaced)
+            }
+            Ok(CreateOutcome::Planned) if replace => {
+                reporting::log_planned(&profile.hostname, true);
+                Ok(CreateOutcome::Planned)
+            }
+            Ok(outcome) => {
+                reporting::log_create_outcome(outcome, &profile.hostname);
+                Ok(outcome)
+            }
+            Err(err) => {
+                if self.rollback {
+                    rollback.undo(provider);
+                } else {
+                    rollback.keep();
+                }
+                Err(err)
+            }
+        }
+    }
+
+    // Unregisters the instance and removes what wslforge left behind for it: its install
+    // dir, its cloud-init user-data file and its state entry. Where the state file recorded
+    // other paths at creation time, those go too. Snapshots and backups are kept.
+    // An instance missing from the state store was not created by wslforge: neither it nor
+    // any of those paths is touched without `force`.
+    // Returns what was removed; in a dry run, what would be.
+    pub fn destroy_instance(&self, profile_name: &str, profile: &Profile, force: bool) -> anyhow::Result<Destroyed> {
+        let profile = &ResolvedProfile::resolve(profile_name, profile)?;
+        let provider = &self.provider_for(profile);
+        let managed = self.managed_instance(&profile.hostname)?;
+        let exists = provider.instance_exists(&profile.hostname)?;
+
+        let mut dirs = vec![profile.instance_dir()];
+        let mut files: Vec<PathBuf> = cloud_init::user_data_path(&profile.hostname).into_iter().collect();
+        if let Some(managed) = &managed {
+            dirs.push(managed.install_dir.join(&profile.hostname));
+            files.extend(managed.cloud_init.clone());
+        }
+        dirs.dedup();
+        dirs.retain(|dir| dir.is_dir());
+        files.dedup();
+        files.retain(|file| file.is_file());
+
+        let unmanaged = self.state.is_some() && managed.is_none();
+        if unmanaged && exists {
+            if !force {
+                anyhow::bail!(
+                    "WSL instance '{}' was not created by wslforge; pass --force to destroy it anyway",
+                    profile.hostname
+                );
+            }
+            warn!("⚠️  WSL instance '{}' was not created by wslforge", profile.hostname);
+        } else if unmanaged && !(dirs.is_empty() && files.is_empty()) {
+            let paths: Vec<String> = dirs
+                .iter()
+                .chain(&files)
+                .map(|path| path.display().to_string())
+                .collect();
+            if !force {
+                anyhow::bail!(
+                    "'{}' was not created by wslforge; pass --force to remove {}",
+                    profile.hostname,
+                    paths.join(", ")
+                );
+            }
+            warn!("⚠️  '{}' was not created by wslforge", profile.hostname);
+        }
+
+        let destroyed = Destroyed {
+            hostname: profile.hostname.clone(),
+            unregistered: exists,
+            removed: dirs.iter().chain(&files).cloned().collect(),
+        };
+        if self.dry_run {
+            return Ok(destroyed);
+        }
+        if exists {
+            provider.delete_instance(&profile.hostname)?;
+        } else {
+            info!("ℹ️ WSL instance '{}' does not exist.", profile.hostname);
+        }
+        for dir in &dirs {
+            std::fs::remove_dir_all(dir).with_context(|| format!("unable to remove {}", dir.display()))?;
+            info!("🗑️ Removed {}", dir.display());
+        }
+        for file in &files {
+            std::fs::remove_file(file).with_context(|| format!("unable to remove {}", file.display()))?;
+            info!("🗑️ Removed {}", file.display());
+        }
+        self.forget(&profile.hostname)?;
+        Ok(destroyed)
+    }
+
+    pub fn list_snapshots(&self, profile_name: &str, profile: &Profile) -> anyhow::Result<Vec<Snapshot>> {
+        let profile = ResolvedProfile::resolve(profile_name, profile)?;
+        SnapshotDir::new(&profile.install_dir, &profile.hostname).list()
+    }
+
+    // `name` defaults to `snapshot-<UTC timestamp>`.
+    pub fn snapshot_instance(
+        &self,
+        profile_name: &str,
+        profile: &Profile,
+        name: Option<&str>,
+    ) -> anyhow::Result<Option<Snapshot>> {
+        let profile = &ResolvedProfile::resolve(profile_name, profile)?;
+        let provider = &self.provider_for(profile);
+        if !provider.instance_exists(&profile.hostname)? {
+            anyhow::bail!("WSL instance '{}' does not exist", profile.hostname);
+        }
+        let snapshots = SnapshotDir::new(&profile.install_dir, &profile.hostname);
+        let name = match name {
+            Some(name) => {
+                snapshots.check_new_name(name)?;
+                name.to_string()
+            }
+            None => snapshots.next_name("snapshot")?,
+        };
+        info!("📸 Taking snapshot '{}' of WSL instance '{}'", name, profile.hostname);
+        self.export_snapshot(provider, profile, &snapshots, &name, SnapshotKind::Snapshot)
+    }
+
+    pub fn remove_snapshot(&self, profile_name: &str, profile: &Profile, name: &str) -> anyhow::Result<()> {
+        let profile = ResolvedProfile::resolve(profile_name, profile)?;
+        let snapshots = SnapshotDir::new(&profile.install_dir, &profile.hostname);
+        let snapshot = snapshots.find(name)?;
+        if self.dry_run {
+            info!("🧪 Dry run: snapshot '{}' would be deleted", snapshot.name);
             return Ok(());
         }
+        snapshots.remove(&snapshot.name)?;
+        info!("🗑️ Snapshot '{}' deleted.", snapshot.name);
+        Ok(())
+    }
 
-        self.prepare_profile(profile)?;
-        reporting::log_config_summary(profile_name, profile);
+    // Imports a snapshot under the profile's instance name. An existing instance is only
+    // replaced with `force`, as restoring discards everything in it.
+    pub fn restore_instance(
+        &self,
+        profile_name: &str,
+        profile: &Profile,
+        snapshot_name: &str,
+        force: bool,
+    ) -> anyhow::Result<()> {
+        let profile = &ResolvedProfile::resolve(profile_name, profile)?;
+        let provider = &self.provider_for(profile);
+        let snapshots = SnapshotDir::new(&profile.install_dir, &profile.hostname);
+        let snapshot = snapshots.find(snapshot_name)?;
+        let archive = snapshots.archive_path(&snapshot);
+        if !archive.is_file() {
+            anyhow::bail!("snapshot archive not found: {}", archive.display());
+        }
 
+        if provider.instance_exists(&profile.hostname)? {
+            if !force {
+                anyhow::bail!(
+                    "WSL instance '{}' exists; pass --force to replace it with snapshot '{}'",
+                    profile.hostname,
+                    snapshot.name
+                );
+            }
+            if self.dry_run {
+                info!("🧪 Dry run: WSL instance '{}' would be deleted", profile.hostname);
+            } else {
+                provider.delete_instance(&profile.hostname)?;
+                self.forget(&profile.hostname)?;
+            }
+        }
         if self.dry_run {
-            info!("🧪 Dry run: WSL instance would be created");
-            reporting::log_create_outcome(CreateOutcome::Skipped, &profile.hostname);
+            info!(
+                "🧪 Dry run: snapshot '{}' would be restored as WSL instance '{}'",
+                snapshot.name, profile.hostname
+            );
             return Ok(());
         }
-        info!("🚀 Creating WSL instance");
-        let outcome = self.create_profile(profile)?;
-        reporting::log_create_outcome(outcome, &profile.hostname);
+
+        info!(
+            "⏪ Restoring snapshot '{}' as WSL instance '{}'",
+            snapshot.name, profile.hostname
+        );
+        provider.create_from_file(&profile.hostname, &profile.instance_dir(), &archive)?;
+        // The instance is what it was when the snapshot was taken, fingerprint included.
+        match snapshot.fingerprint {
+            Some(fingerprint) => self.remember(profile, fingerprint, None),
+            None => self.forget(&profile.hostname)?,
+        }
+        info!("✅ WSL instance '{}' restored.", profile.hostname);
         Ok(())
     }
 
-    fn delete_instance(&self, hostname: &str, instance_exists: bool) -> anyhow::Result<()> {
-        if !instance_exists {
-            info!("ℹ️ WSL instance '{}' does not exist. Skipping delete.", hostname);
+    pub fn list_instances(&self) -> anyhow::Result<Vec<InstanceInfo>> {
+        self.provider.list_instances()
+    }
+
+    pub fn instance_exists(&self, hostname: &str) -> anyhow::Result<bool> {
+        self.provider.instance_exists(hostname)
+    }
+
+    pub fn export_instance(&self, hostname: &str, archive: &std::path::Path) -> anyhow::Result<()> {
+        if self.dry_run {
+            info!(
+                "🧪 Dry run: WSL instance '{}' would be exported to {}",
+                hostname,
+                archive.display()
+            );
             return Ok(());
-        } else {
-            info!("⚠️ WSL instance '{}' already exists and will be overridden.", hostname);
         }
+        self.provider.export(hostname, archive)
+    }
+
+    pub fn terminate_instance(&self, hostname: &str) -> anyhow::Result<()> {
         if self.dry_run {
-            info!("🧪 Dry run: WSL instance '{}' would be deleted", hostname);
+            info!("🧪 Dry run: WSL instance '{}' would be terminated", hostname);
+            return Ok(());
+        }
+        self.provider.terminate(hostname)
+    }
+
+    pub fn shutdown(&self) -> anyhow::Result<()> {
+        if self.dry_run {
+            info!("🧪 Dry run: WSL would be shut down");
+            return Ok(());
+        }
+        self.provider.shutdown()
+    }
+
+    pub fn set_default_instance(&self, hostname: &str) -> anyhow::Result<()> {
+        if self.dry_run {
+            info!("🧪 Dry run: WSL instance '{}' would be set as default", hostname);
             return Ok(());
         }
-        self.provider.delete_instance(hostname)
+        self.provider.set_default(hostname)
+    }
+
+    pub fn set_instance_version(&self, hostname: &str, version: u8) -> anyhow::Result<()> {
+        if self.dry_run {
+            info!(
+                "🧪 Dry run: WSL instance '{}' would be converted to WSL {}",
+                hostname, version
+            );
+            return Ok(());
+        }
+        self.provider.set_version(hostname, version)
+    }
+
+    // Not affected by dry run: the command is the caller's, and may well be read-only.
+    pub fn run_command(&self, hostname: &str, command: &[String]) -> anyhow::Result<CommandOutput> {
+        self.provider.run_command(hostname, command)
     }
 
-    fn prepare_profile(&self, profile: &Profile) -> anyhow::Result<()> {
-        validation::validate_image_source(profile)?;
-        cloud_init::prepare_cloud_init(profile, self.dry_run, self.debug)?;
+    pub fn render_cloud_init(&self, profile_name: &str, profile: &Profile) -> anyhow::Result<Option<String>> {
+        cloud_init::render_profile_cloud_init(&ResolvedProfile::resolve(profile_name, profile)?)
+    }
+
+    // The provider for one profile's operations, with its `timeouts` applied.
+    fn provider_for(&self, profile: &ResolvedProfile) -> provider::WslProvider {
+        self.provider
+            .with_timeouts(profile.timeouts.as_ref().unwrap_or(&Timeouts::default()))
+    }
+
+    // Exports the instance about to be overridden, then prunes backups beyond the profile's
+    // retention. A failed export stops the override: the backup is the point.
+    fn back_up_instance(&self, provider: &provider::WslProvider, profile: &ResolvedProfile) -> anyhow::Result<()> {
+        let snapshots = SnapshotDir::new(&profile.install_dir, &profile.hostname);
+        let name = snapshots.next_name("backup")?;
+        info!("💾 Backing up WSL instance '{}' before overriding it", profile.hostname);
+        self.export_snapshot(provider, profile, &snapshots, &name, SnapshotKind::Backup)
+            .with_context(|| {
+                format!(
+                    "backup of WSL instance '{}' failed; not overriding it",
+                    profile.hostname
+                )
+            })?;
+        if !self.dry_run {
+            for removed in snapshots.prune(SnapshotKind::Backup, profile.backup_retention)? {
+                info!("🧹 Removed old backup '{}'", removed);
+            }
+        }
         Ok(())
     }
 
-    fn create_profile(&self, profile: &Profile) -> anyhow::Result<CreateOutcome> {
+    // Exports the instance into its snapshot directory and adds it to the manifest, with the
+    // fingerprint the state file has for it. `None` in a dry run.
+    fn export_snapshot(
+        &self,
+        provider: &provider::WslProvider,
+        profile: &ResolvedProfile,
+        snapshots: &SnapshotDir,
+        name: &str,
+        kind: SnapshotKind,
+    ) -> anyhow::Result<Option<Snapshot>> {
+        let archive = snapshots.archive_for(name);
+        if self.dry_run {
+            info!(
+                "🧪 Dry run: WSL instance '{}' would be exported to {}",
+                profile.hostname,
+                archive.display()
+            );
+            return Ok(None);
+        }
+        snapshots.create_dir()?;
+        if let Err(err) = provider.export(&profile.hostname, &archive) {
+            let _ = std::fs::remove_file(&archive);
+            return Err(err);
+        }
+        let fingerprint = self
+            .managed_instance(&profile.hostname)?
+            .map(|managed| managed.fingerprint);
+        snapshots.add(name, kind, fingerprint).map(Some)
+    }
+
+    fn delete_instance(&self, provider: &provider::WslProvider, hostname: &str) -> anyhow::Result<()> {
+        info!("⚠️ WSL instance '{}' already exists and will be overridden.", hostname);
+        if self.state.is_some() && self.managed_instance(hostname)?.is_none() {
+            warn!("⚠️  WSL instance '{}' was not created by wslforge", hostname);
+        }
+        if self.dry_run {
+            info!("🧪 Dry run: WSL instance '{}' would be deleted", hostname);
+            return Ok(());
+        }
+        provider.delete_instance(hostname)?;
+        self.forget(hostname)
+    }
+
+    pub fn managed_instance(&self, hostname: &str) -> anyhow::Result<Option<ManagedInstance>> {
+        match &self.state {
+            Some(state) => state.get(hostname),
+            None => Ok(None),
+        }
+    }
+
+    fn drift(&self, profile: &ResolvedProfile) -> anyhow::Result<Drift> {
+        let Some(recorded) = self.managed_instance(&profile.hostname)? else {
+            return Ok(Drift::Unknown);
+        };
+        Ok(match fingerprint::fingerprint(profile)? == recorded.fingerprint {
+            true => Drift::Unchanged,
+            false => Drift::Changed,
+        })
+    }
+
+    // Called once the instance exists. A failed write only loses the record, so it is a
+    // warning: undoing a good instance over it would be worse.
+    fn remember(&self, profile: &ResolvedProfile, fingerprint: String, cloud_init: Option<PathBuf>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let recorded = state.record(ManagedInstance {
+            profile: profile.name.clone(),
+            hostname: profile.hostname.clone(),
+            fingerprint,
+            image: profile.image.clone(),
+            created_at: timestamp(),
+            install_dir: profile.install_dir.clone(),
+            cloud_init,
+        });
+        if let Err(err) = recorded {
+            warn!(
+                "⚠️  WSL instance '{}' was created but not recorded in {}: {err:#}",
+                profile.hostname,
+                state.path().display()
+            );
+        }
+    }
+
+    fn forget(&self, hostname: &str) -> anyhow::Result<()> {
+        match &self.state {
+            Some(state) => state.forget(hostname),
+            None => Ok(()),
+        }
+    }
+
+    // Everything `apply` would check before touching WSL, minus writing the user-data file.
+    fn plan_profile(&self, profile: &ResolvedProfile) -> anyhow::Result<(PlanAction, String)> {
+        validation::validate_profile_names(profile)?;
+        let provider = &self.provider_for(profile);
+        let instance_exists = provider.instance_exists(&profile.hostname)?;
+        let (action, mut reason) = plan::decide(profile, instance_exists, || self.drift(profile))?;
+        if action.is_change() {
+            validation::validate_image_source(profile, provider)?;
+            cloud_init::render_profile_cloud_init(profile)?;
+        }
+        if action == PlanAction::Replace && (profile.backup_before_override || self.backup_before_override) {
+            reason.push_str("; backed up first");
+        }
+        Ok((action, reason))
+    }
+
+    // Every step from writing the user-data file on, recording in `rollback` what each one
+    // leaves behind.
+    fn provision(
+        &self,
+        provider: &provider::WslProvider,
+        profile: &ResolvedProfile,
+        rollback: &mut Rollback,
+    ) -> anyhow::Result<CreateOutcome> {
+        validation::validate_image_source(profile, provider)?;
+        let user_data = cloud_init::prepare_cloud_init(profile, self.dry_run, self.debug)?;
+        if let Some(path) = &user_data {
+            rollback.user_data(path.clone());
+        }
+        reporting::log_config_summary(profile);
+
+        if self.dry_run {
+            return Ok(CreateOutcome::Planned);
+        }
+        // Worked out before creating anything, so that nothing after the import can fail.
+        let fingerprint = fingerprint::fingerprint(profile)?;
+        if matches!(profile.image, ImageSource::File { .. }) && !profile.instance_dir().exists() {
+            rollback.install_dir(profile.instance_dir());
+        }
+        // Before the call: a failed `--import` or `--install` can leave a half-registered
+        // instance behind.
+        rollback.instance(&profile.hostname);
+        info!("🚀 Creating WSL instance");
+        let outcome = self.create_profile(provider, profile)?;
+        self.remember(profile, fingerprint, user_data);
+        Ok(outcome)
+    }
+
+    fn create_profile(
+        &self,
+        provider: &provider::WslProvider,
+        profile: &ResolvedProfile,
+    ) -> anyhow::Result<CreateOutcome> {
         match &profile.image {
             ImageSource::File { path: rootfs_tar } => {
-                let install_dir = profile.install_dir.join(&profile.hostname);
-                self.provider
-                    .create_from_file(&profile.hostname, &install_dir, rootfs_tar)
+                provider.create_from_file(&profile.hostname, &profile.instance_dir(), rootfs_tar)
             }
-            ImageSource::Distro { name } => self.provider.create_from_distro(name, &profile.hostname),
+            ImageSource::Distro { name } => provider.create_from_distro(name, &profile.hostname),
         }
     }
 }
@@ -1,9 +1,31 @@
+mod apply;
 mod cloud_init;
 mod engine;
-mod helpers;
+mod fingerprint;
+pub(crate) mod helpers;
 mod manager;
+mod plan;
 mod provider;
 mod reporting;
-mod validation;
+mod resolve;
+mod rollback;
+mod runner;
+mod snapshots;
+mod state;
+pub(crate) mod validation;
 
-pub use manager::WslManager;
+pub use apply::{ApplyReport, ApplyResult};
+pub use engine::{CommandOutput, CreateOutcome, InstanceInfo, InstanceState};
+pub use manager::{Destroyed, WslManager};
+pub use plan::{Plan, PlanAction, PlanEntry};
+pub use provider::{EngineKind, EngineOptions};
+pub use reporting::{
+    log_prefix, print_apply_summary, print_destroy_plan, print_plan, print_profile_list, print_snapshots, print_status,
+};
+pub use resolve::ResolvedProfile;
+pub use runner::{
+    install_interrupt_handler, is_interrupted, CommandRunner, Interrupted, Invocation, ProcessOutput, RecordingRunner,
+    ReplayRunner, SystemRunner, TimedOut,
+};
+pub use snapshots::{Snapshot, SnapshotKind};
+pub use state::{ManagedInstance, State, StateStore};
@@ -0,0 +1,88 @@
+use crate::config::OverridePolicy;
+use crate::wsl::resolve::ResolvedProfile;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanAction {
+    Create,
+    Replace,
+    Skip,
+    Error,
+}
+
+impl PlanAction {
+    pub fn is_change(self) -> bool {
+        matches!(self, PlanAction::Create | PlanAction::Replace)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlanEntry {
+    pub profile: String,
+    pub hostname: String,
+    pub action: PlanAction,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Plan {
+    pub entries: Vec<PlanEntry>,
+}
+
+impl Plan {
+    pub fn has_changes(&self) -> bool {
+        self.entries.iter().any(|entry| entry.action.is_change())
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.entries.iter().any(|entry| entry.action == PlanAction::Error)
+    }
+
+    pub fn count(&self, action: PlanAction) -> usize {
+        self.entries.iter().filter(|entry| entry.action == action).count()
+    }
+}
+
+// How an existing instance compares with its profile, for `override: if_changed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Drift {
+    Unchanged,
+    Changed,
+    // wslforge has no record of creating the instance, so there is nothing to compare.
+    Unknown,
+}
+
+// The create/replace/skip decision shared by `plan` and `apply`. It only looks at the
+// profile and the current instance state, so it never has side effects. `drift` only
+// matters for `if_changed`; the caller works it out lazily.
+pub(crate) fn decide(
+    profile: &ResolvedProfile,
+    instance_exists: bool,
+    drift: impl FnOnce() -> anyhow::Result<Drift>,
+) -> anyhow::Result<(PlanAction, String)> {
+    if !instance_exists {
+        return Ok((PlanAction::Create, "instance does not exist".into()));
+    }
+    Ok(match profile.override_instance {
+        OverridePolicy::Always => (
+            PlanAction::Replace,
+            "instance exists and `override: always` is set".into(),
+        ),
+        OverridePolicy::Never => (
+            PlanAction::Skip,
+            "instance already exists and `override` is `never`".into(),
+        ),
+        OverridePolicy::IfChanged => match drift()? {
+            Drift::Changed => (
+                PlanAction::Replace,
+                "profile, cloud-init or image changed since the instance was created".into(),
+            ),
+            Drift::Unchanged => (PlanAction::Skip, "instance is up to date".into()),
+            Drift::Unknown => (
+                PlanAction::Skip,
+                "instance was not created by wslforge, so `if_changed` cannot compare it".into(),
+            ),
+        },
+    })
+}
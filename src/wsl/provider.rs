@@ -1,11 +1,47 @@
+use crate::config::Timeouts;
 use crate::wsl::engine::api::ApiEngine;
 use crate::wsl::engine::cli::CliEngine;
-use crate::wsl::engine::{CreateOutcome, WslEngine};
-use log::info;
+use crate::wsl::engine::fake::FakeEngine;
+use crate::wsl::engine::{CommandOutput, CreateOutcome, InstanceInfo, WslEngine};
+use crate::wsl::runner::{CommandRunner, SystemRunner};
+use log::{debug, info};
+use std::path::PathBuf;
+use std::sync::Arc;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
 pub enum EngineKind {
+    /// Drive wsl.exe
+    #[default]
     Cli,
+    /// Native WSL API (not implemented yet)
     Api,
+    /// Simulated WSL keeping its instances in a JSON state file, for CI and demos
+    Fake,
+}
+
+// How engines reach WSL.
+#[derive(Clone)]
+pub struct EngineOptions {
+    pub wsl_exe: PathBuf,
+    pub runner: Arc<dyn CommandRunner>,
+    pub timeouts: Timeouts,
+    // Let `wsl --install` draw its progress on the terminal. Off when profiles run in
+    // parallel, where several of them would interleave.
+    pub inherit_output: bool,
+    // The fake engine's state file, over `WSLFORGE_FAKE_STATE`.
+    pub fake_state: Option<PathBuf>,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            wsl_exe: PathBuf::from("wsl.exe"),
+            runner: Arc::new(SystemRunner),
+            timeouts: Timeouts::default(),
+            inherit_output: true,
+            fake_state: None,
+        }
+    }
 }
 
 pub struct WslProvider {
@@ -13,14 +49,40 @@ pub struct WslProvider {
 }
 
 impl WslProvider {
-    pub fn new(kind: EngineKind) -> Self {
+    pub fn new(kind: EngineKind, options: &EngineOptions) -> Self {
         let engine: Box<dyn WslEngine> = match kind {
-            EngineKind::Cli => Box::new(CliEngine::new()),
+            EngineKind::Cli => Box::new(CliEngine::new(options)),
             EngineKind::Api => Box::new(ApiEngine::new()),
+            EngineKind::Fake => Box::new(FakeEngine::new(options)),
         };
         Self { engine }
     }
 
+    // For one profile's operations, with its own `timeouts` applied.
+    pub fn with_timeouts(&self, overrides: &Timeouts) -> WslProvider {
+        WslProvider {
+            engine: self.engine.with_timeouts(overrides),
+        }
+    }
+
+    pub fn check_available(&self) -> anyhow::Result<()> {
+        self.engine.check_available()
+    }
+
+    pub fn check_environment(&self, dry_run: bool) -> anyhow::Result<()> {
+        self.engine.check_environment(dry_run)
+    }
+
+    pub fn is_known_distro(&self, name: &str) -> anyhow::Result<bool> {
+        self.engine.is_known_distro(name)
+    }
+
+    pub fn list_instances(&self) -> anyhow::Result<Vec<InstanceInfo>> {
+        let instances = self.engine.list_instances()?;
+        debug!("📋 WSL instances: {:?}", instances);
+        Ok(instances)
+    }
+
     pub fn instance_exists(&self, name: &str) -> anyhow::Result<bool> {
         info!("🔍 Checking if WSL instance '{}' exists...", name);
         let exists = self.engine.instance_exists(name)?;
@@ -57,4 +119,36 @@ impl WslProvider {
         self.engine.create_from_distro(distro_name, hostname)?;
         Ok(CreateOutcome::Created)
     }
+
+    pub fn export(&self, name: &str, archive: &std::path::Path) -> anyhow::Result<()> {
+        info!("📦 Exporting WSL instance '{}' to {}", name, archive.display());
+        self.engine.export(name, archive)?;
+        info!("✅ WSL instance '{}' exported.", name);
+        Ok(())
+    }
+
+    pub fn terminate(&self, name: &str) -> anyhow::Result<()> {
+        info!("⏹️ Terminating WSL instance '{}'", name);
+        self.engine.terminate(name)
+    }
+
+    pub fn shutdown(&self) -> anyhow::Result<()> {
+        info!("⏹️ Shutting down WSL");
+        self.engine.shutdown()
+    }
+
+    pub fn set_default(&self, name: &str) -> anyhow::Result<()> {
+        info!("⭐ Setting WSL instance '{}' as default", name);
+        self.engine.set_default(name)
+    }
+
+    pub fn set_version(&self, name: &str, version: u8) -> anyhow::Result<()> {
+        info!("🔄 Converting WSL instance '{}' to WSL {}", name, version);
+        self.engine.set_version(name, version)
+    }
+
+    pub fn run_command(&self, name: &str, command: &[String]) -> anyhow::Result<CommandOutput> {
+        debug!("▶️ Running in WSL instance '{}': {:?}", name, command);
+        self.engine.run_command(name, command)
+    }
 }
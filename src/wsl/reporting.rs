@@ -1,28 +1,65 @@
 use crate::config::{ImageSource, Profile};
-use crate::wsl::engine::CreateOutcome;
-use crate::wsl::helpers::expand_env_vars;
-use log::info;
+use crate::redact;
+use crate::wsl::apply::ApplyReport;
+use crate::wsl::engine::{CreateOutcome, InstanceInfo};
+use crate::wsl::manager::Destroyed;
+use crate::wsl::plan::{Plan, PlanAction};
+use crate::wsl::resolve::ResolvedProfile;
+use crate::wsl::snapshots::{Snapshot, SnapshotKind};
+use log::{error, info};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+thread_local! {
+    static LOG_PREFIX: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// The profile whose work the current thread is doing, when profiles run in parallel; the
+// logger puts it in front of every line.
+pub fn log_prefix() -> Option<String> {
+    LOG_PREFIX.with(|prefix| prefix.borrow().clone())
+}
+
+pub(crate) fn with_log_prefix<T>(prefix: &str, work: impl FnOnce() -> T) -> T {
+    let previous = LOG_PREFIX.with(|current| current.replace(Some(prefix.to_string())));
+    let result = work();
+    LOG_PREFIX.with(|current| *current.borrow_mut() = previous);
+    result
+}
 
 pub fn log_create_outcome(outcome: CreateOutcome, hostname: &str) {
     match outcome {
         CreateOutcome::Created => {
             info!("✅ WSL instance '{}' created successfully.", hostname);
         }
+        CreateOutcome::Replaced => {
+            info!("✅ WSL instance '{}' replaced successfully.", hostname);
+        }
         CreateOutcome::AlreadyExists => {
             info!("ℹ️ WSL instance '{}' already exists.", hostname);
         }
-        CreateOutcome::Skipped => {
-            info!("ℹ️ WSL instance '{}' was skipped.", hostname);
+        CreateOutcome::Planned => log_planned(hostname, false),
+        CreateOutcome::Failed => {
+            error!("⛔ WSL instance '{}' could not be created.", hostname);
         }
     }
 }
 
-pub fn log_config_summary(profile_name: &str, profile: &Profile) {
-    info!("🧩 Profile: {}", profile_name);
+// A dry run's outcome; `replace` when the instance exists and would be replaced.
+pub(crate) fn log_planned(hostname: &str, replace: bool) {
+    let action = if replace { "replaced" } else { "created" };
+    info!("🧪 Dry run: WSL instance '{}' would be {}.", hostname, action);
+}
+
+pub fn log_config_summary(profile: &ResolvedProfile) {
+    info!("🧩 Profile: {}", profile.name);
     info!("♻️ Override: {}", profile.override_instance);
     info!("🏷️ Hostname: {}", profile.hostname);
     info!("👤 User: {}", profile.username);
-    info!("📦 Install dir: {}", expand_install_dir(profile));
+    if let Some(password) = &profile.password {
+        info!("🔑 Password: {}", password);
+    }
+    info!("📦 Install dir: {}", profile.install_dir.display());
     match &profile.cloud_init {
         Some(source) => info!("☁️ Cloud-init: {}", source),
         None => info!("☁️ Cloud-init: not configured"),
@@ -38,14 +75,208 @@ pub fn log_config_summary(profile_name: &str, profile: &Profile) {
     }
 
     if let Some(proxy) = &profile.http_proxy {
-        info!("🌐 HTTP proxy: {}", proxy);
+        info!("🌐 HTTP proxy: {}", redact::url(proxy));
     }
     if let Some(proxy) = &profile.https_proxy {
-        info!("🔐 HTTPS proxy: {}", proxy);
+        info!("🔐 HTTPS proxy: {}", redact::url(proxy));
+    }
+}
+
+pub fn print_profile_list(profiles: &BTreeMap<String, Profile>) {
+    println!("{:<24} {:<24} IMAGE", "PROFILE", "HOSTNAME");
+    for (profile_name, profile) in profiles {
+        println!(
+            "{:<24} {:<24} {}",
+            profile_name,
+            profile.hostname,
+            describe_image(&profile.image)
+        );
+    }
+}
+
+pub fn print_status(rows: &[(String, String, Option<InstanceInfo>)]) {
+    println!("{:<24} {:<24} {:<12} VERSION", "PROFILE", "HOSTNAME", "STATUS");
+    for (profile_name, hostname, instance) in rows {
+        match instance {
+            Some(instance) => {
+                let default = if instance.is_default { " (default)" } else { "" };
+                println!(
+                    "{:<24} {:<24} {:<12} {}{}",
+                    profile_name,
+                    hostname,
+                    instance.state.to_string(),
+                    instance.version,
+                    default
+                );
+            }
+            None => println!("{:<24} {:<24} {:<12} -", profile_name, hostname, "absent"),
+        }
+    }
+}
+
+pub fn print_snapshots(snapshots: &[Snapshot]) {
+    println!("{:<32} {:<10} {:<22} SIZE", "NAME", "KIND", "CREATED");
+    for snapshot in snapshots {
+        println!(
+            "{:<32} {:<10} {:<22} {}",
+            snapshot.name,
+            describe_snapshot_kind(snapshot.kind),
+            snapshot.created_at,
+            describe_size(snapshot.size)
+        );
+    }
+}
+
+pub fn print_plan(plan: &Plan) {
+    println!("{:<24} {:<24} {:<8} REASON", "PROFILE", "HOSTNAME", "ACTION");
+    for entry in &plan.entries {
+        println!(
+            "{:<24} {:<24} {:<8} {}",
+            entry.profile,
+            entry.hostname,
+            describe_action(entry.action),
+            entry.reason
+        );
     }
+    println!();
+    println!(
+        "Plan: {} to create, {} to replace, {} unchanged, {} error(s).",
+        plan.count(PlanAction::Create),
+        plan.count(PlanAction::Replace),
+        plan.count(PlanAction::Skip),
+        plan.count(PlanAction::Error)
+    );
 }
 
-fn expand_install_dir(profile: &Profile) -> String {
-    expand_env_vars(&profile.install_dir.to_string_lossy())
-        .unwrap_or_else(|_| profile.install_dir.to_string_lossy().into_owned())
+// What a dry-run `destroy` would do, one line per instance and path.
+pub fn print_destroy_plan(destroyed: &Destroyed) {
+    if destroyed.unregistered {
+        println!("Would unregister WSL instance '{}'", destroyed.hostname);
+    }
+    for path in &destroyed.removed {
+        println!("Would remove {}", path.display());
+    }
+    if !destroyed.unregistered && destroyed.removed.is_empty() {
+        println!("Nothing to destroy for '{}'", destroyed.hostname);
+    }
+}
+
+// `total` is the number of profiles in the config, to point out the ones never attempted
+// after an early stop.
+pub fn print_apply_summary(report: &ApplyReport, total: usize) {
+    println!("{:<24} {:<24} {:<10} DURATION", "PROFILE", "HOSTNAME", "OUTCOME");
+    for result in &report.results {
+        println!(
+            "{:<24} {:<24} {:<10} {:.1}s",
+            result.profile,
+            result.hostname,
+            describe_outcome(result.outcome),
+            result.duration.as_secs_f64()
+        );
+    }
+    println!();
+    println!(
+        "Apply: {} created, {} replaced, {} unchanged, {} planned, {} failed.",
+        report.count(CreateOutcome::Created),
+        report.count(CreateOutcome::Replaced),
+        report.count(CreateOutcome::AlreadyExists),
+        report.count(CreateOutcome::Planned),
+        report.count(CreateOutcome::Failed)
+    );
+    if let Some(note) = stopped_early_note(report, total) {
+        println!("{}", note);
+    }
+}
+
+// `--keep-going` only helps with failures; after Ctrl-C it would not have run any more.
+fn stopped_early_note(report: &ApplyReport, total: usize) -> Option<String> {
+    let not_attempted = total - report.results.len();
+    if not_attempted == 0 {
+        return None;
+    }
+    Some(match report.was_interrupted() {
+        true => format!("Cancelled: {} profile(s) not attempted.", not_attempted),
+        false => format!(
+            "Stopped early: {} profile(s) not attempted (use --keep-going to run them).",
+            not_attempted
+        ),
+    })
+}
+
+fn describe_outcome(outcome: CreateOutcome) -> &'static str {
+    match outcome {
+        CreateOutcome::Created => "created",
+        CreateOutcome::Replaced => "replaced",
+        CreateOutcome::AlreadyExists => "unchanged",
+        CreateOutcome::Planned => "planned",
+        CreateOutcome::Failed => "failed",
+    }
+}
+
+fn describe_snapshot_kind(kind: SnapshotKind) -> &'static str {
+    match kind {
+        SnapshotKind::Backup => "backup",
+        SnapshotKind::Snapshot => "snapshot",
+    }
+}
+
+fn describe_size(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn describe_action(action: PlanAction) -> &'static str {
+    match action {
+        PlanAction::Create => "create",
+        PlanAction::Replace => "replace",
+        PlanAction::Skip => "skip",
+        PlanAction::Error => "error",
+    }
+}
+
+fn describe_image(image: &ImageSource) -> String {
+    match image {
+        ImageSource::Distro { name } => format!("distro: {}", name),
+        ImageSource::File { path } => format!("file: {}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wsl::apply::ApplyResult;
+    use crate::wsl::runner::Interrupted;
+    use std::time::Duration;
+
+    fn failed(error: anyhow::Error) -> ApplyReport {
+        ApplyReport {
+            results: vec![ApplyResult {
+                profile: "dev".into(),
+                hostname: "dev".into(),
+                outcome: CreateOutcome::Failed,
+                duration: Duration::ZERO,
+                error: Some(error),
+            }],
+        }
+    }
+
+    #[test]
+    fn suggests_keep_going_after_a_failure() {
+        let note = stopped_early_note(&failed(anyhow::anyhow!("import failed")), 3);
+        assert_eq!(
+            note.as_deref(),
+            Some("Stopped early: 2 profile(s) not attempted (use --keep-going to run them).")
+        );
+    }
+
+    #[test]
+    fn does_not_suggest_keep_going_after_ctrl_c() {
+        let error = anyhow::Error::new(Interrupted).context("unable to import");
+        let note = stopped_early_note(&failed(error), 3);
+        assert_eq!(note.as_deref(), Some("Cancelled: 2 profile(s) not attempted."));
+    }
+
+    #[test]
+    fn says_nothing_when_every_profile_ran() {
+        assert_eq!(stopped_early_note(&failed(anyhow::anyhow!("import failed")), 1), None);
+    }
 }
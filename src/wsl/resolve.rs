@@ -0,0 +1,106 @@
+// A `Profile` as the rest of `wsl` consumes it: every path and free-form string field has
+// gone through `expand_env_vars` once, up front, so validation, cloud-init rendering and
+// the engine all see the same values.
+// - Serialized into the cloud-init template as `profile`, with the same field names as
+//   `Profile` so existing templates keep working.
+// - `password` stays a lazily-read source and is never serialized; only the path of the
+//   `file` form is expanded (a plaintext password may legitimately contain `$`).
+// - Not expanded: inline cloud-init content (it is a template) and proxy URLs (already
+//   validated as URLs when loading).
+use crate::config::{CloudInitSource, ImageSource, OverridePolicy, PasswordSource, Profile, Timeouts};
+use crate::wsl::helpers::expand_env_vars;
+use anyhow::Context;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedProfile {
+    #[serde(skip)]
+    pub name: String,
+    #[serde(skip_serializing_if = "OverridePolicy::is_never", rename = "override")]
+    pub override_instance: OverridePolicy,
+    #[serde(skip)]
+    pub backup_before_override: bool,
+    #[serde(skip)]
+    pub backup_retention: usize,
+    pub hostname: String,
+    pub username: String,
+    #[serde(skip)]
+    pub password: Option<PasswordSource>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_proxy: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub https_proxy: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_proxy: Option<String>,
+
+    pub install_dir: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_init: Option<CloudInitSource>,
+    pub image: ImageSource,
+    #[serde(skip)]
+    pub timeouts: Option<Timeouts>,
+}
+
+impl ResolvedProfile {
+    pub fn resolve(name: &str, profile: &Profile) -> anyhow::Result<Self> {
+        Self::expand(name, profile).with_context(|| format!("unable to resolve profile '{}'", name))
+    }
+
+    fn expand(name: &str, profile: &Profile) -> anyhow::Result<Self> {
+        let cloud_init = match &profile.cloud_init {
+            Some(CloudInitSource::File { path }) => Some(CloudInitSource::File {
+                path: expand_path(path)?,
+            }),
+            other => other.clone(),
+        };
+        let image = match &profile.image {
+            ImageSource::Distro { name } => ImageSource::Distro {
+                name: expand_env_vars(name)?,
+            },
+            ImageSource::File { path } => ImageSource::File {
+                path: expand_path(path)?,
+            },
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            override_instance: profile.override_instance,
+            backup_before_override: profile.backup_before_override,
+            backup_retention: profile.backup_retention,
+            hostname: expand_env_vars(&profile.hostname)?,
+            username: expand_env_vars(&profile.username)?,
+            password: profile.password.as_ref().map(expand_password).transpose()?,
+            http_proxy: profile.http_proxy.clone(),
+            https_proxy: profile.https_proxy.clone(),
+            no_proxy: profile.no_proxy.as_deref().map(expand_env_vars).transpose()?,
+            install_dir: expand_path(&profile.install_dir)?,
+            cloud_init,
+            image,
+            timeouts: profile.timeouts.clone(),
+        })
+    }
+
+    // Where `wsl --import` places this instance's virtual disk.
+    pub fn instance_dir(&self) -> PathBuf {
+        self.install_dir.join(&self.hostname)
+    }
+}
+
+fn expand_password(source: &PasswordSource) -> anyhow::Result<PasswordSource> {
+    match source {
+        PasswordSource::File { file } => Ok(PasswordSource::File {
+            file: expand_path(file)?,
+        }),
+        PasswordSource::Hash { hash } if !hash.starts_with('$') => {
+            anyhow::bail!("password hash must be in crypt(3) format, e.g. \"$6$...\"")
+        }
+        other => Ok(other.clone()),
+    }
+}
+
+fn expand_path(path: &Path) -> anyhow::Result<PathBuf> {
+    expand_env_vars(&path.to_string_lossy()).map(PathBuf::from)
+}
@@ -0,0 +1,69 @@
+// What one `create_instance` call has registered with WSL or put on disk so far, so that a
+// failure can undo exactly that. Things that existed before the call are never recorded.
+use crate::wsl::provider::WslProvider;
+use crate::wsl::runner;
+use log::{info, warn};
+use std::path::PathBuf;
+
+#[derive(Debug, Default)]
+pub(crate) struct Rollback {
+    user_data: Option<PathBuf>,
+    install_dir: Option<PathBuf>,
+    instance: Option<String>,
+}
+
+impl Rollback {
+    pub fn user_data(&mut self, path: PathBuf) {
+        self.user_data = Some(path);
+    }
+
+    pub fn install_dir(&mut self, path: PathBuf) {
+        self.install_dir = Some(path);
+    }
+
+    pub fn instance(&mut self, name: &str) {
+        self.instance = Some(name.to_string());
+    }
+
+    // Best effort: every step is attempted and only logged when it fails, since the error
+    // worth reporting is the one that triggered the rollback. Runs even after Ctrl-C.
+    // The instance goes first, as WSL keeps its disk in the install dir open.
+    pub fn undo(self, provider: &WslProvider) {
+        if let Some(name) = &self.instance {
+            let result = runner::shielded(|| match provider.instance_exists(name)? {
+                true => {
+                    info!("↩️ Rollback: unregistering WSL instance '{}'", name);
+                    provider.delete_instance(name)
+                }
+                false => Ok(()),
+            });
+            if let Err(err) = result {
+                warn!("⚠️  Rollback: could not unregister WSL instance '{}': {err:#}", name);
+            }
+        }
+        if let Some(dir) = &self.install_dir {
+            match std::fs::remove_dir_all(dir) {
+                Ok(()) => info!("↩️ Rollback: removed install dir {}", dir.display()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => warn!("⚠️  Rollback: could not remove {}: {err}", dir.display()),
+            }
+        }
+        if let Some(file) = &self.user_data {
+            match std::fs::remove_file(file) {
+                Ok(()) => info!("↩️ Rollback: removed {}", file.display()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => warn!("⚠️  Rollback: could not remove {}: {err}", file.display()),
+            }
+        }
+    }
+
+    // `--no-rollback`: say what is being left behind.
+    pub fn keep(self) {
+        if let Some(name) = &self.instance {
+            warn!("⚠️  Rollback disabled: WSL instance '{}' may be left registered", name);
+        }
+        for path in [&self.install_dir, &self.user_data].into_iter().flatten() {
+            warn!("⚠️  Rollback disabled: leaving {}", path.display());
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Ctrl-C handling. The first Ctrl-C only raises a flag: the running child is killed by
+// `SystemRunner`, the error unwinds normally and cleanup code wrapped in `shielded` still
+// gets to run its commands. A second Ctrl-C exits at once.
+use std::cell::Cell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static SHIELDED: Cell<bool> = const { Cell::new(false) };
+}
+
+// How often a wait checks for Ctrl-C.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Ctrl-C was pressed while a command was running.
+#[derive(Debug)]
+pub struct Interrupted;
+
+/// A command ran past its timeout and was killed.
+#[derive(Debug)]
+pub struct TimedOut {
+    pub command: String,
+    pub after: Duration,
+}
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "interrupted by Ctrl-C")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` timed out after {}",
+            self.command,
+            humantime::format_duration(self.after)
+        )
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+pub fn install_interrupt_handler() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+        eprintln!("⛔ Interrupted, stopping the running command (press Ctrl-C again to exit now)");
+    })?;
+    Ok(())
+}
+
+// Whether commands on this thread should stop because of Ctrl-C.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst) && !SHIELDED.with(Cell::get)
+}
+
+// Runs cleanup that must not be cut short by an earlier Ctrl-C. Timeouts still apply.
+pub fn shielded<T>(cleanup: impl FnOnce() -> T) -> T {
+    let previous = SHIELDED.with(|shielded| shielded.replace(true));
+    let result = cleanup();
+    SHIELDED.with(|shielded| shielded.set(previous));
+    result
+}
+
+// A sleep that gives up early on Ctrl-C.
+pub(crate) fn sleep(duration: Duration) -> anyhow::Result<()> {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if is_interrupted() {
+            return Err(Interrupted.into());
+        }
+        std::thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+// Every external program wslforge drives (wsl.exe, dism.exe, wslpath) is started through
+// a `CommandRunner`, so there is one place to observe, record or fake process execution.
+// - `SystemRunner` spawns real processes.
+// - `RecordingRunner` wraps it and writes each invocation to a transcript file.
+// - `ReplayRunner` answers from a transcript instead of spawning anything, so the
+//   Windows-only code paths can be exercised anywhere.
+// `run_with_retry` adds the policy for wsl.exe's management verbs on top: a per-operation
+// timeout and bounded retries of errors known to be transient. `run_once` is for commands
+// that must not be repeated, such as a user's command inside an instance. Ctrl-C (see
+// `cancel`) kills whatever child is running.
+use crate::config::Timeouts;
+use log::warn;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+mod cancel;
+mod system;
+mod transcript;
+
+pub use cancel::{install_interrupt_handler, is_interrupted, shielded, Interrupted, TimedOut};
+pub use system::SystemRunner;
+pub use transcript::{RecordingRunner, ReplayRunner};
+
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+// Messages wsl.exe prints for conditions that clear up on their own, compared lowercase.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "the service is busy",
+    "service cannot accept control messages at this time",
+    "the operation timed out because a response was not received from the virtual machine",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Install,
+    Import,
+    Update,
+    Export,
+    Other,
+    // A command run inside an instance; not a wsl.exe management verb.
+    Command,
+}
+
+impl Operation {
+    // `None` waits for as long as it takes.
+    pub fn timeout(self, timeouts: &Timeouts) -> Option<Duration> {
+        const MINUTE: u64 = 60;
+        let (configured, fallback) = match self {
+            Operation::Install => (timeouts.install, 60 * MINUTE),
+            Operation::Import => (timeouts.import, 30 * MINUTE),
+            Operation::Update => (timeouts.update, 15 * MINUTE),
+            Operation::Export => (timeouts.export, 60 * MINUTE),
+            Operation::Other => (timeouts.default, 5 * MINUTE),
+            Operation::Command => return timeouts.command,
+        };
+        Some(configured.unwrap_or(Duration::from_secs(fallback)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    // Also pass the child's output on to our terminal as it arrives (e.g. download
+    // progress). It is captured either way, so errors in it can still be recognised.
+    pub inherit_output: bool,
+    // Kill the child after this long; `None` waits forever.
+    pub timeout: Option<Duration>,
+}
+
+impl Invocation {
+    pub fn new(program: impl AsRef<Path>) -> Self {
+        Self {
+            program: program.as_ref().to_path_buf(),
+            args: Vec::new(),
+            inherit_output: false,
+            timeout: None,
+        }
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.args.extend(args.into_iter().map(|arg| arg.as_ref().to_string()));
+        self
+    }
+
+    pub fn inherit_output(mut self) -> Self {
+        self.inherit_output = true;
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program.display())?;
+        for arg in &self.args {
+            write!(f, " {arg}")?;
+        }
+        Ok(())
+    }
+}
+
+// What a finished process produced. `exit_code` is `None` when it was killed by a signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessOutput {
+    pub exit_code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ProcessOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+
+    // For error messages, in the spirit of `ExitStatus`'s `Display`.
+    pub fn status(&self) -> String {
+        match self.exit_code {
+            Some(code) => format!("exit code: {code}"),
+            None => "terminated by signal".into(),
+        }
+    }
+}
+
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, invocation: &Invocation) -> anyhow::Result<ProcessOutput>;
+    // Whether `program` can be started here; the error says why not.
+    fn check_program(&self, program: &Path) -> anyhow::Result<()>;
+}
+
+// Runs `invocation` with the timeout of `operation`, retrying a bounded number of times
+// while it fails with a known-transient error. Timeouts and Ctrl-C are not retried.
+pub fn run_with_retry(
+    runner: &dyn CommandRunner,
+    invocation: Invocation,
+    operation: Operation,
+    timeouts: &Timeouts,
+) -> anyhow::Result<ProcessOutput> {
+    let invocation = invocation.timeout(operation.timeout(timeouts));
+    let mut attempt = 1;
+    loop {
+        let output = runner.run(&invocation)?;
+        if output.success() || attempt == MAX_ATTEMPTS || !is_transient(&output) {
+            return Ok(output);
+        }
+        warn!(
+            "⚠️  `{}` hit a transient error, retrying in {}s (attempt {}/{})",
+            invocation,
+            RETRY_DELAY.as_secs(),
+            attempt + 1,
+            MAX_ATTEMPTS
+        );
+        cancel::sleep(RETRY_DELAY)?;
+        attempt += 1;
+    }
+}
+
+// Runs `invocation` exactly once with the timeout of `operation`, whatever it prints.
+pub fn run_once(
+    runner: &dyn CommandRunner,
+    invocation: Invocation,
+    operation: Operation,
+    timeouts: &Timeouts,
+) -> anyhow::Result<ProcessOutput> {
+    runner.run(&invocation.timeout(operation.timeout(timeouts)))
+}
+
+fn is_transient(output: &ProcessOutput) -> bool {
+    let text = format!(
+        "{}\n{}",
+        crate::wsl::helpers::decode_wsl_output(&output.stdout),
+        crate::wsl::helpers::decode_wsl_output(&output.stderr)
+    )
+    .to_lowercase();
+    TRANSIENT_ERRORS.iter().any(|pattern| text.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_commands_have_no_timeout_unless_configured() {
+        let defaults = Timeouts::default();
+        assert_eq!(Operation::Command.timeout(&defaults), None);
+        assert_eq!(Operation::Other.timeout(&defaults), Some(Duration::from_secs(300)));
+
+        let configured = Timeouts {
+            command: Some(Duration::from_secs(7200)),
+            ..Timeouts::default()
+        };
+        assert_eq!(Operation::Command.timeout(&configured), Some(Duration::from_secs(7200)));
+        assert_eq!(Operation::Other.timeout(&configured), Some(Duration::from_secs(300)));
+    }
+}
@@ -0,0 +1,206 @@
+use crate::wsl::helpers::{find_executable, wsl_interop_enabled, wsl_output_encoding};
+use crate::wsl::runner::cancel::POLL_INTERVAL;
+use crate::wsl::runner::{is_interrupted, CommandRunner, Interrupted, Invocation, ProcessOutput, TimedOut};
+use anyhow::Context;
+use encoding_rs::Decoder;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, invocation: &Invocation) -> anyhow::Result<ProcessOutput> {
+        if is_interrupted() {
+            return Err(Interrupted.into());
+        }
+        let mut command = Command::new(&invocation.program);
+        command
+            .args(&invocation.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("unable to run {}", invocation.program.display()))?;
+
+        // Pipes are drained on their own threads so a chatty child cannot block on a full
+        // pipe while we wait for it.
+        let echo = invocation.inherit_output;
+        let stdout = child
+            .stdout
+            .take()
+            .map(|pipe| drain(pipe, echo.then(|| boxed(std::io::stdout()))));
+        let stderr = child
+            .stderr
+            .take()
+            .map(|pipe| drain(pipe, echo.then(|| boxed(std::io::stderr()))));
+        let status = wait(&mut child, invocation)?;
+        Ok(ProcessOutput {
+            exit_code: status.code(),
+            stdout: stdout.map(join).unwrap_or_default(),
+            stderr: stderr.map(join).unwrap_or_default(),
+        })
+    }
+
+    // Windows executables run natively on Windows, and from inside WSL through interop. A
+    // stand-in (e.g. a shim script for tests) works anywhere as long as it can be found.
+    fn check_program(&self, program: &Path) -> anyhow::Result<()> {
+        let Some(found) = find_executable(program) else {
+            anyhow::bail!("'{}' not found", program.display());
+        };
+        let is_windows_exe = found
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("exe"));
+        if !cfg!(windows) && is_windows_exe && !wsl_interop_enabled() {
+            anyhow::bail!(
+                "'{}' is a Windows executable, but WSL interop is not available here",
+                found.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+// Waits for the child, killing it on timeout or Ctrl-C.
+fn wait(child: &mut Child, invocation: &Invocation) -> anyhow::Result<ExitStatus> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if is_interrupted() {
+            kill(child);
+            return Err(Interrupted.into());
+        }
+        if let Some(timeout) = invocation.timeout {
+            if started.elapsed() >= timeout {
+                kill(child);
+                return Err(TimedOut {
+                    command: invocation.to_string(),
+                    after: timeout,
+                }
+                .into());
+            }
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn kill(child: &mut Child) {
+    // Fails only if it already exited, which is what we want anyway.
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// Collects everything the child writes to `pipe`, passing it on to `echo` as it arrives.
+// wsl.exe writes UTF-16LE to a pipe, so the echo is decoded; the capture stays raw.
+fn drain(mut pipe: impl Read + Send + 'static, echo: Option<Box<dyn Write + Send>>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut echo = echo.map(Echo::new);
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            match pipe.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+            if let Some(echo) = &mut echo {
+                echo.write(&buffer, false);
+            }
+        }
+        if let Some(echo) = &mut echo {
+            echo.write(&buffer, true);
+        }
+        buffer
+    })
+}
+
+fn boxed(target: impl Write + Send + 'static) -> Box<dyn Write + Send> {
+    Box::new(target)
+}
+
+struct Echo {
+    target: Box<dyn Write + Send>,
+    // Picked once the first two bytes are in.
+    decoder: Option<Decoder>,
+    written: usize,
+}
+
+impl Echo {
+    fn new(target: Box<dyn Write + Send>) -> Self {
+        Self {
+            target,
+            decoder: None,
+            written: 0,
+        }
+    }
+
+    // Writes what `buffer` gained since the last call; `last` flushes a trailing partial
+    // character.
+    fn write(&mut self, buffer: &[u8], last: bool) {
+        if self.decoder.is_none() && (buffer.len() >= 2 || (last && !buffer.is_empty())) {
+            self.decoder = Some(wsl_output_encoding(buffer).new_decoder());
+        }
+        let Some(decoder) = &mut self.decoder else {
+            return;
+        };
+        let new = &buffer[self.written..];
+        let mut text = String::with_capacity(decoder.max_utf8_buffer_length(new.len()).unwrap_or(new.len() * 3));
+        let _ = decoder.decode_to_string(new, &mut text, last);
+        self.written = buffer.len();
+        let _ = self.target.write_all(text.as_bytes());
+        let _ = self.target.flush();
+    }
+}
+
+fn join(handle: JoinHandle<Vec<u8>>) -> Vec<u8> {
+    handle.join().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn echoes_utf16le_output_as_text_across_chunk_boundaries() {
+        let utf16: Vec<u8> = "Installing: Ubuntü\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let shown = Shared::default();
+        let mut echo = Echo::new(Box::new(shown.clone()));
+
+        // A byte at a time splits every code unit, and the first byte alone is too short to sniff.
+        let mut buffer = Vec::new();
+        for chunk in utf16.chunks(1) {
+            buffer.extend_from_slice(chunk);
+            echo.write(&buffer, false);
+        }
+        echo.write(&buffer, true);
+
+        assert_eq!(
+            String::from_utf8(shown.0.lock().unwrap().clone()).unwrap(),
+            "Installing: Ubuntü\r\n"
+        );
+    }
+}
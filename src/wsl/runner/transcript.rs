@@ -0,0 +1,271 @@
+// Transcripts: a JSON file listing every invocation in order, with its exit code and raw
+// output. Output is stored as text where that is lossless (wsl.exe's UTF-16LE or UTF-8)
+// so transcripts stay readable in bug reports, and as bytes otherwise.
+// Replay matches invocations strictly in order, by program file name and arguments, so a
+// transcript replays wherever wsl.exe happens to live on the replaying machine. Whether
+// each program could be started is recorded too, keyed by file name, so replay takes the
+// same branches (e.g. skipping the feature checks when there was no dism.exe).
+use crate::wsl::runner::{CommandRunner, Invocation, ProcessOutput, SystemRunner};
+use anyhow::Context;
+use encoding_rs::UTF_16LE;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Transcript {
+    // File name -> why it could not be started, or `null` when it could.
+    #[serde(default)]
+    programs: BTreeMap<String, Option<String>>,
+    commands: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    program: PathBuf,
+    args: Vec<String>,
+    exit_code: Option<i32>,
+    stdout: Stream,
+    stderr: Stream,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Stream {
+    Utf8(String),
+    Utf16le(String),
+    Bytes(Vec<u8>),
+}
+
+impl Stream {
+    fn capture(bytes: &[u8]) -> Self {
+        // UTF-16LE ASCII text has a NUL in every second byte; UTF-8 text never has NULs.
+        let looks_utf16 = bytes.iter().skip(1).step_by(2).any(|b| *b == 0);
+        if looks_utf16 {
+            if let Some(text) = UTF_16LE.decode_without_bom_handling_and_without_replacement(bytes) {
+                return Stream::Utf16le(text.into_owned());
+            }
+        }
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Stream::Utf8(text.to_string()),
+            Err(_) => Stream::Bytes(bytes.to_vec()),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Stream::Utf8(text) => text.as_bytes().to_vec(),
+            Stream::Utf16le(text) => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            Stream::Bytes(bytes) => bytes.clone(),
+        }
+    }
+}
+
+// Split on both separators: a transcript recorded with `C:\...\wsl.exe` replays on Linux.
+fn program_key(program: &Path) -> String {
+    let program = program.to_string_lossy();
+    program.rsplit(['/', '\\']).next().unwrap_or_default().to_string()
+}
+
+pub struct RecordingRunner {
+    inner: SystemRunner,
+    path: PathBuf,
+    transcript: Mutex<Transcript>,
+}
+
+impl RecordingRunner {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            inner: SystemRunner,
+            path: path.to_path_buf(),
+            transcript: Mutex::new(Transcript::default()),
+        }
+    }
+
+    // Rewritten after every change, so a run that fails half-way still leaves everything up
+    // to the failure on disk.
+    fn save(&self, transcript: &Transcript) -> anyhow::Result<()> {
+        std::fs::write(&self.path, serde_json::to_string_pretty(transcript)?)
+            .with_context(|| format!("unable to write transcript: {}", self.path.display()))
+    }
+}
+
+impl CommandRunner for RecordingRunner {
+    fn run(&self, invocation: &Invocation) -> anyhow::Result<ProcessOutput> {
+        let output = self.inner.run(invocation)?;
+        let mut transcript = self.transcript.lock().unwrap_or_else(|e| e.into_inner());
+        transcript.commands.push(Entry {
+            program: invocation.program.clone(),
+            args: invocation.args.clone(),
+            exit_code: output.exit_code,
+            stdout: Stream::capture(&output.stdout),
+            stderr: Stream::capture(&output.stderr),
+        });
+        self.save(&transcript)?;
+        Ok(output)
+    }
+
+    fn check_program(&self, program: &Path) -> anyhow::Result<()> {
+        let result = self.inner.check_program(program);
+        let mut transcript = self.transcript.lock().unwrap_or_else(|e| e.into_inner());
+        let problem = result.as_ref().err().map(|err| format!("{err:#}"));
+        transcript.programs.insert(program_key(program), problem);
+        self.save(&transcript)?;
+        result
+    }
+}
+
+pub struct ReplayRunner {
+    path: PathBuf,
+    programs: BTreeMap<String, Option<String>>,
+    remaining: Mutex<VecDeque<Entry>>,
+}
+
+impl ReplayRunner {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content =
+            std::fs::read_to_string(path).with_context(|| format!("unable to read transcript: {}", path.display()))?;
+        let transcript: Transcript =
+            serde_json::from_str(&content).with_context(|| format!("invalid transcript: {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            programs: transcript.programs,
+            remaining: Mutex::new(transcript.commands.into()),
+        })
+    }
+}
+
+impl CommandRunner for ReplayRunner {
+    fn run(&self, invocation: &Invocation) -> anyhow::Result<ProcessOutput> {
+        let mut remaining = self.remaining.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(entry) = remaining.pop_front() else {
+            anyhow::bail!(
+                "transcript {} has no more commands, but `{}` was run",
+                self.path.display(),
+                invocation
+            );
+        };
+        if program_key(&entry.program) != program_key(&invocation.program) || entry.args != invocation.args {
+            let expected = Invocation::new(&entry.program).args(&entry.args);
+            anyhow::bail!(
+                "transcript {} expected `{}` next, but `{}` was run",
+                self.path.display(),
+                expected,
+                invocation
+            );
+        }
+        Ok(ProcessOutput {
+            exit_code: entry.exit_code,
+            stdout: entry.stdout.bytes(),
+            stderr: entry.stderr.bytes(),
+        })
+    }
+
+    // A program the transcript knows nothing about is assumed to be available.
+    fn check_program(&self, program: &Path) -> anyhow::Result<()> {
+        match self.programs.get(&program_key(program)) {
+            Some(Some(problem)) => anyhow::bail!("{}", problem),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(dir: &tempfile::TempDir, commands: &[(&str, &[&str], &[u8])]) -> PathBuf {
+        let transcript = Transcript {
+            programs: BTreeMap::from([("dism.exe".to_string(), Some("dism.exe: program not found".to_string()))]),
+            commands: commands
+                .iter()
+                .map(|(program, args, stdout)| Entry {
+                    program: program.into(),
+                    args: args.iter().map(ToString::to_string).collect(),
+                    exit_code: Some(0),
+                    stdout: Stream::capture(stdout),
+                    stderr: Stream::capture(b""),
+                })
+                .collect(),
+        };
+        let path = dir.path().join("transcript.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&transcript).unwrap()).unwrap();
+        path
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn replays_output_byte_for_byte_in_order() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let listing = utf16("  NAME  STATE  VERSION\r\n");
+        let path = transcript(
+            &dir,
+            &[
+                ("wsl.exe", &["--list", "--verbose"], &listing),
+                ("wsl.exe", &["-d", "dev", "--", "echo", "hé"], "hé\n".as_bytes()),
+                ("wsl.exe", &["--export", "dev", "out.tar"], &[0xff, 0x00, 0xfe]),
+            ],
+        );
+        let runner = ReplayRunner::load(&path).unwrap();
+
+        let run = |args: &[&str]| runner.run(&Invocation::new("wsl.exe").args(args)).unwrap().stdout;
+        assert_eq!(run(&["--list", "--verbose"]), listing);
+        assert_eq!(run(&["-d", "dev", "--", "echo", "hé"]), "hé\n".as_bytes());
+        assert_eq!(run(&["--export", "dev", "out.tar"]), [0xff, 0x00, 0xfe]);
+    }
+
+    #[test]
+    fn matches_programs_by_file_name_on_either_platform() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = transcript(&dir, &[("C:\\Windows\\System32\\wsl.exe", &["--status"], b"")]);
+        let runner = ReplayRunner::load(&path).unwrap();
+
+        assert!(runner
+            .run(&Invocation::new("/mnt/c/Windows/system32/wsl.exe").args(["--status"]))
+            .is_ok());
+    }
+
+    #[test]
+    fn fails_loudly_on_an_argv_mismatch() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = transcript(&dir, &[("wsl.exe", &["--unregister", "dev"], b"")]);
+        let runner = ReplayRunner::load(&path).unwrap();
+
+        let err = runner
+            .run(&Invocation::new("wsl.exe").args(["--unregister", "prod"]))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "transcript {} expected `wsl.exe --unregister dev` next, but `wsl.exe --unregister prod` was run",
+                path.display()
+            )
+        );
+    }
+
+    #[test]
+    fn fails_loudly_on_a_program_mismatch_and_past_the_end() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = transcript(&dir, &[("wsl.exe", &["--status"], b"")]);
+        let runner = ReplayRunner::load(&path).unwrap();
+
+        let err = runner.run(&Invocation::new("dism.exe").args(["--status"])).unwrap_err();
+        assert!(err.to_string().contains("expected `wsl.exe --status` next"), "{err}");
+        let err = runner.run(&Invocation::new("wsl.exe").args(["--status"])).unwrap_err();
+        assert!(err.to_string().contains("has no more commands"), "{err}");
+    }
+
+    #[test]
+    fn replays_whether_programs_could_start() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let runner = ReplayRunner::load(&transcript(&dir, &[])).unwrap();
+
+        let err = runner.check_program(Path::new("dism.exe")).unwrap_err();
+        assert_eq!(err.to_string(), "dism.exe: program not found");
+        assert!(runner.check_program(Path::new("wsl.exe")).is_ok());
+    }
+}
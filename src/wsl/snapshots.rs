@@ -0,0 +1,186 @@
+// Archives of an instance made with `wsl --export`, kept next to its disk in
+// `<install_dir>/<hostname>.snapshots/`. A `manifest.json` there lists them, oldest first,
+// with what is needed to restore one: the archive, its size and the fingerprint the
+// instance had in the state file when it was taken.
+// Backups are taken before `override` replaces an instance, and pruned to the profile's
+// `backup_retention`; snapshots are taken on request and kept until removed.
+use crate::wsl::helpers::timestamp;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapshotKind {
+    // Taken automatically before an override.
+    Backup,
+    // Taken with `wslforge snapshot`.
+    Snapshot,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub kind: SnapshotKind,
+    // File name within the snapshot directory.
+    pub archive: String,
+    pub created_at: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    snapshots: Vec<Snapshot>,
+}
+
+pub(crate) struct SnapshotDir {
+    dir: PathBuf,
+}
+
+impl SnapshotDir {
+    pub fn new(install_dir: &Path, hostname: &str) -> Self {
+        Self {
+            dir: install_dir.join(format!("{}.snapshots", hostname)),
+        }
+    }
+
+    pub fn list(&self) -> anyhow::Result<Vec<Snapshot>> {
+        Ok(self.load()?.snapshots)
+    }
+
+    pub fn find(&self, name: &str) -> anyhow::Result<Snapshot> {
+        let snapshots = self.list()?;
+        match snapshots.iter().find(|snapshot| snapshot.name == name) {
+            Some(snapshot) => Ok(snapshot.clone()),
+            None if snapshots.is_empty() => {
+                anyhow::bail!("no snapshot '{}': there are none in {}", name, self.dir.display())
+            }
+            None => anyhow::bail!(
+                "no snapshot '{}' in {}; available: {}",
+                name,
+                self.dir.display(),
+                snapshots.iter().map(|s| s.name.as_str()).collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    pub fn archive_path(&self, snapshot: &Snapshot) -> PathBuf {
+        self.dir.join(&snapshot.archive)
+    }
+
+    pub fn archive_for(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.tar", name))
+    }
+
+    // A fresh `<prefix>-<UTC timestamp>` name.
+    pub fn next_name(&self, prefix: &str) -> anyhow::Result<String> {
+        let stamp: String = timestamp().chars().filter(|c| c.is_ascii_alphanumeric()).collect();
+        let mut name = format!("{}-{}", prefix, stamp);
+        let mut counter = 2;
+        while self.is_taken(&name)? {
+            name = format!("{}-{}-{}", prefix, stamp, counter);
+            counter += 1;
+        }
+        Ok(name)
+    }
+
+    // Names become file names, so they are kept to characters that are safe everywhere.
+    pub fn check_new_name(&self, name: &str) -> anyhow::Result<()> {
+        let valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            anyhow::bail!(
+                "invalid snapshot name '{}': use letters, digits, '-', '_' and '.', not starting with '.'",
+                name
+            );
+        }
+        if self.is_taken(name)? {
+            anyhow::bail!("snapshot '{}' already exists in {}", name, self.dir.display());
+        }
+        Ok(())
+    }
+
+    fn is_taken(&self, name: &str) -> anyhow::Result<bool> {
+        Ok(self.list()?.iter().any(|snapshot| snapshot.name == name) || self.archive_for(name).exists())
+    }
+
+    pub fn create_dir(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("unable to create snapshot directory: {}", self.dir.display()))
+    }
+
+    // Records an archive already written to `archive_path`.
+    pub fn add(&self, name: &str, kind: SnapshotKind, fingerprint: Option<String>) -> anyhow::Result<Snapshot> {
+        let archive = format!("{}.tar", name);
+        let size = std::fs::metadata(self.archive_for(name))
+            .map(|m| m.len())
+            .unwrap_or_default();
+        let snapshot = Snapshot {
+            name: name.to_string(),
+            kind,
+            archive,
+            created_at: timestamp(),
+            size,
+            fingerprint,
+        };
+        let mut manifest = self.load()?;
+        manifest.snapshots.push(snapshot.clone());
+        self.save(&manifest)?;
+        Ok(snapshot)
+    }
+
+    pub fn remove(&self, name: &str) -> anyhow::Result<()> {
+        let snapshot = self.find(name)?;
+        match std::fs::remove_file(self.archive_path(&snapshot)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(err).with_context(|| format!("unable to delete snapshot archive '{}'", snapshot.archive))
+            }
+            _ => {}
+        }
+        let mut manifest = self.load()?;
+        manifest.snapshots.retain(|snapshot| snapshot.name != name);
+        self.save(&manifest)
+    }
+
+    // Deletes the oldest snapshots of `kind` beyond the newest `keep`; returns their names.
+    pub fn prune(&self, kind: SnapshotKind, keep: usize) -> anyhow::Result<Vec<String>> {
+        let of_kind: Vec<String> = self
+            .list()?
+            .into_iter()
+            .filter(|snapshot| snapshot.kind == kind)
+            .map(|snapshot| snapshot.name)
+            .collect();
+        let excess = of_kind.len().saturating_sub(keep);
+        let removed: Vec<String> = of_kind.into_iter().take(excess).collect();
+        for name in &removed {
+            self.remove(name)?;
+        }
+        Ok(removed)
+    }
+
+    fn load(&self) -> anyhow::Result<Manifest> {
+        let path = self.dir.join(MANIFEST);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                serde_json::from_str(&content).with_context(|| format!("invalid snapshot manifest: {}", path.display()))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::default()),
+            Err(err) => Err(err).with_context(|| format!("unable to read snapshot manifest: {}", path.display())),
+        }
+    }
+
+    fn save(&self, manifest: &Manifest) -> anyhow::Result<()> {
+        self.create_dir()?;
+        let path = self.dir.join(MANIFEST);
+        std::fs::write(&path, serde_json::to_string_pretty(manifest)?)
+            .with_context(|| format!("unable to write snapshot manifest: {}", path.display()))
+    }
+}
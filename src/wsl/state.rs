@@ -0,0 +1,106 @@
+// What wslforge remembers about the instances it created, so it can tell them from
+// same-named instances created by hand and see whether their profile changed since.
+// - One JSON file per user: `%LOCALAPPDATA%\wslforge\state.json`, or
+//   `~/.local/state/wslforge/state.json` where there is no `LOCALAPPDATA` (inside WSL).
+// - Keyed by instance name in lowercase, as WSL compares names case-insensitively.
+// - Rewritten through a temporary file, so an interrupted run never leaves half a file.
+use crate::config::ImageSource;
+use crate::wsl::validation::instance_key;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub instances: BTreeMap<String, ManagedInstance>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManagedInstance {
+    pub profile: String,
+    pub hostname: String,
+    pub fingerprint: String,
+    pub image: ImageSource,
+    // RFC 3339, UTC.
+    pub created_at: String,
+    pub install_dir: PathBuf,
+    // The user-data file written for the instance's first boot, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_init: Option<PathBuf>,
+}
+
+pub struct StateStore {
+    path: PathBuf,
+    // Serializes load-modify-save cycles between `apply --jobs` threads.
+    lock: Mutex<()>,
+}
+
+impl StateStore {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn default_path() -> anyhow::Result<PathBuf> {
+        if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+            return Ok(PathBuf::from(dir).join("wslforge").join("state.json"));
+        }
+        match std::env::var_os("HOME") {
+            Some(home) => Ok(PathBuf::from(home).join(".local/state/wslforge/state.json")),
+            None => anyhow::bail!("neither LOCALAPPDATA nor HOME is set; pass --state to choose the state file"),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self) -> anyhow::Result<State> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => {
+                serde_json::from_str(&content).with_context(|| format!("invalid state file: {}", self.path.display()))
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(err) => Err(err).with_context(|| format!("unable to read state file: {}", self.path.display())),
+        }
+    }
+
+    pub fn get(&self, hostname: &str) -> anyhow::Result<Option<ManagedInstance>> {
+        Ok(self.load()?.instances.remove(&instance_key(hostname)))
+    }
+
+    pub fn record(&self, instance: ManagedInstance) -> anyhow::Result<()> {
+        self.update(|state| {
+            state.instances.insert(instance_key(&instance.hostname), instance);
+        })
+    }
+
+    pub fn forget(&self, hostname: &str) -> anyhow::Result<()> {
+        self.update(|state| {
+            state.instances.remove(&instance_key(hostname));
+        })
+    }
+
+    fn update(&self, change: impl FnOnce(&mut State)) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = self.load()?;
+        change(&mut state);
+        self.save(&state)
+    }
+
+    fn save(&self, state: &State) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("unable to create state directory: {}", dir.display()))?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(state)?)
+            .and_then(|()| std::fs::rename(&temp, &self.path))
+            .with_context(|| format!("unable to write state file: {}", self.path.display()))
+    }
+}
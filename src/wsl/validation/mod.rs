@@ -1,18 +1,46 @@
-use crate::config::{ImageSource, Profile};
-use encoding_rs::UTF_16LE;
+use crate::config::ImageSource;
+use crate::config::Timeouts;
+use crate::wsl::helpers::decode_wsl_output;
+use crate::wsl::provider::WslProvider;
+use crate::wsl::resolve::ResolvedProfile;
+use crate::wsl::runner::{run_with_retry, CommandRunner, Invocation, Operation};
 use log::{debug, info, warn};
-use std::process::Command;
+use std::path::Path;
 
-pub fn validate_environment(dry_run: bool) -> anyhow::Result<()> {
-    validate_wsl_installed()?;
-    update_wsl_version(dry_run)?;
-    validate_windows_features(&["Microsoft-Windows-Subsystem-Linux", "VirtualMachinePlatform"])?;
+mod names;
+
+const DISM_EXE: &str = "dism.exe";
+
+pub use names::{
+    distro_name_problem, hostname_problem, instance_key, username_problem, validate_profile_names,
+    validate_unique_names,
+};
+
+pub fn validate_environment(
+    runner: &dyn CommandRunner,
+    wsl_exe: &Path,
+    timeouts: &Timeouts,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    validate_wsl_installed(runner, wsl_exe, timeouts)?;
+    update_wsl_version(runner, wsl_exe, timeouts, dry_run)?;
+    // Only reachable on Windows or through interop; a wsl.exe stand-in has no dism.exe.
+    if let Err(err) = runner.check_program(Path::new(DISM_EXE)) {
+        warn!("⚠️  Skipping Windows feature checks: {err:#}");
+        return Ok(());
+    }
+    validate_windows_features(
+        runner,
+        timeouts,
+        &["Microsoft-Windows-Subsystem-Linux", "VirtualMachinePlatform"],
+    )?;
     Ok(())
 }
 
-pub fn validate_wsl_installed() -> anyhow::Result<()> {
-    let output = Command::new("wsl.exe").arg("--status").output()?;
-    if output.status.success() {
+pub fn validate_wsl_installed(runner: &dyn CommandRunner, wsl_exe: &Path, timeouts: &Timeouts) -> anyhow::Result<()> {
+    let invocation = Invocation::new(wsl_exe).args(["--status"]);
+    let output = run_with_retry(runner, invocation, Operation::Other, timeouts)?;
+    if output.success() {
         info!("✅ WSL is installed");
         Ok(())
     } else {
@@ -20,13 +48,19 @@ pub fn validate_wsl_installed() -> anyhow::Result<()> {
     }
 }
 
-pub fn update_wsl_version(dry_run: bool) -> anyhow::Result<()> {
+pub fn update_wsl_version(
+    runner: &dyn CommandRunner,
+    wsl_exe: &Path,
+    timeouts: &Timeouts,
+    dry_run: bool,
+) -> anyhow::Result<()> {
     if dry_run {
         info!("🧪 Dry run: WSL update would be performed");
         return Ok(());
     }
-    let output = Command::new("wsl.exe").arg("--update").output()?;
-    if output.status.success() {
+    let invocation = Invocation::new(wsl_exe).args(["--update"]);
+    let output = run_with_retry(runner, invocation, Operation::Update, timeouts)?;
+    if output.success() {
         info!("✅ WSL update completed");
         Ok(())
     } else {
@@ -34,7 +68,7 @@ pub fn update_wsl_version(dry_run: bool) -> anyhow::Result<()> {
     }
 }
 
-pub fn validate_image_source(profile: &Profile) -> anyhow::Result<()> {
+pub fn validate_image_source(profile: &ResolvedProfile, provider: &WslProvider) -> anyhow::Result<()> {
     match &profile.image {
         ImageSource::File { path } => {
             if !path.exists() {
@@ -48,7 +82,7 @@ pub fn validate_image_source(profile: &Profile) -> anyhow::Result<()> {
             }
         }
         ImageSource::Distro { name } => {
-            if !is_valid_wsl_distro_name(name)? {
+            if !provider.is_known_distro(name)? {
                 anyhow::bail!("unknown WSL distro name: {name}");
             }
         }
@@ -56,10 +90,14 @@ pub fn validate_image_source(profile: &Profile) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn validate_windows_features(feature_names: &[&str]) -> anyhow::Result<()> {
+pub fn validate_windows_features(
+    runner: &dyn CommandRunner,
+    timeouts: &Timeouts,
+    feature_names: &[&str],
+) -> anyhow::Result<()> {
     let mut disabled = Vec::new();
     for feature_name in feature_names {
-        match is_windows_feature_enabled(feature_name)? {
+        match is_windows_feature_enabled(runner, timeouts, feature_name)? {
             true => info!("✅ {feature_name} is enabled"),
             false => {
                 warn!("⚠️  {feature_name} is not enabled");
@@ -73,7 +111,7 @@ pub fn validate_windows_features(feature_names: &[&str]) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn is_likely_rootfs_archive(path: &std::path::Path) -> bool {
+fn is_likely_rootfs_archive(path: &Path) -> bool {
     let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_lowercase();
     name.ends_with(".tar") || name.ends_with(".tar.gz") || name.ends_with(".tgz")
 }
@@ -82,14 +120,20 @@ fn is_likely_rootfs_archive(path: &std::path::Path) -> bool {
 // OS interaction helpers
 //
 
-fn is_valid_wsl_distro_name(name: &str) -> anyhow::Result<bool> {
-    let output = Command::new("wsl.exe").args(["--list", "--online"]).output()?;
+pub(crate) fn is_valid_wsl_distro_name(
+    runner: &dyn CommandRunner,
+    wsl_exe: &Path,
+    timeouts: &Timeouts,
+    name: &str,
+) -> anyhow::Result<bool> {
+    let invocation = Invocation::new(wsl_exe).args(["--list", "--online"]);
+    let output = run_with_retry(runner, invocation, Operation::Other, timeouts)?;
 
-    if !output.status.success() {
-        anyhow::bail!("wsl.exe --list --online failed with status {}", output.status);
+    if !output.success() {
+        anyhow::bail!("wsl.exe --list --online failed with {}", output.status());
     }
 
-    let (text, _, _) = UTF_16LE.decode(&output.stdout);
+    let text = decode_wsl_output(&output.stdout);
 
     let ids: Vec<String> = text
         .lines()
@@ -104,21 +148,24 @@ fn is_valid_wsl_distro_name(name: &str) -> anyhow::Result<bool> {
     Ok(ids.iter().any(|id| id.eq_ignore_ascii_case(name)))
 }
 
-fn is_windows_feature_enabled(feature_name: &str) -> anyhow::Result<bool> {
-    let output = Command::new("dism.exe")
-        .args([
-            "/English",
-            "/online",
-            "/Get-FeatureInfo",
-            &format!("/featureName:{feature_name}"),
-        ])
-        .output()?;
-
-    if !output.status.success() {
+fn is_windows_feature_enabled(
+    runner: &dyn CommandRunner,
+    timeouts: &Timeouts,
+    feature_name: &str,
+) -> anyhow::Result<bool> {
+    let invocation = Invocation::new(DISM_EXE).args([
+        "/English",
+        "/online",
+        "/Get-FeatureInfo",
+        &format!("/featureName:{feature_name}"),
+    ]);
+    let output = run_with_retry(runner, invocation, Operation::Other, timeouts)?;
+
+    if !output.success() {
         let stdout = String::from_utf8_lossy(&output.stdout);
         anyhow::bail!(
-            "dism.exe failed for feature '{feature_name}' with status {}\n{}",
-            output.status,
+            "dism.exe failed for feature '{feature_name}' with {}\n{}",
+            output.status(),
             stdout.trim(),
         );
     }
@@ -126,3 +173,47 @@ fn is_windows_feature_enabled(feature_name: &str) -> anyhow::Result<bool> {
     let stdout = String::from_utf8_lossy(&output.stdout);
     Ok(stdout.lines().any(|line| line.trim() == "State : Enabled"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wsl::runner::ReplayRunner;
+
+    fn check_environment(transcript: &str, dry_run: bool) -> anyhow::Result<()> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/transcripts")
+            .join(transcript);
+        let runner = ReplayRunner::load(&path).unwrap();
+        validate_environment(&runner, Path::new("wsl.exe"), &Timeouts::default(), dry_run)
+    }
+
+    #[test]
+    fn passes_with_wsl_up_to_date_and_features_enabled() {
+        check_environment("environment-ready.json", false).unwrap();
+    }
+
+    #[test]
+    fn reports_disabled_windows_features() {
+        let err = check_environment("environment-feature-disabled.json", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "required Windows feature(s) are disabled: VirtualMachinePlatform"
+        );
+    }
+
+    #[test]
+    fn skips_feature_checks_without_dism() {
+        check_environment("environment-no-dism.json", false).unwrap();
+    }
+
+    #[test]
+    fn dry_run_does_not_update_wsl() {
+        // The transcript expects `--update` after `--status`; a dry run goes to dism instead.
+        let err = check_environment("environment-ready.json", true).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("expected `wsl.exe --update` next, but `dism.exe"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+// Naming rules for what a profile ends up creating. A profile's `hostname` is used twice:
+// as the WSL distro name (`wsl --import <name>`) and as the cloud-init `hostname:`, so it
+// has to satisfy both rules. `username` becomes a Linux account created by cloud-init.
+// Each check returns a short reason so callers can report it their own way.
+use crate::wsl::resolve::ResolvedProfile;
+use std::collections::BTreeMap;
+
+// RFC 1123 limits: a label is at most 63 characters, the whole name at most 253.
+const MAX_LABEL_LEN: usize = 63;
+const MAX_HOSTNAME_LEN: usize = 253;
+// The `useradd` limit for account names.
+const MAX_USERNAME_LEN: usize = 32;
+
+// WSL accepts letters, digits, `.`, `_` and `-`. A leading `-` would be read as a flag by
+// `wsl.exe -d <name>`, and `.`/`..` are not usable as the install folder name.
+pub fn distro_name_problem(name: &str) -> Option<String> {
+    if name.is_empty() {
+        return Some("must not be empty".into());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    {
+        return Some(format!(
+            "'{c}' is not allowed in a WSL distro name (letters, digits, `.`, `_` and `-` only)"
+        ));
+    }
+    if name.starts_with('-') {
+        return Some("must not start with `-`".into());
+    }
+    if name == "." || name == ".." {
+        return Some(format!("'{name}' is not a usable WSL distro name"));
+    }
+    None
+}
+
+// RFC 1123: dot-separated labels of letters, digits and `-`, not starting or ending with
+// `-`. Underscores are not allowed.
+pub fn hostname_problem(hostname: &str) -> Option<String> {
+    if hostname.is_empty() {
+        return Some("must not be empty".into());
+    }
+    if hostname.len() > MAX_HOSTNAME_LEN {
+        return Some(format!("is longer than {MAX_HOSTNAME_LEN} characters"));
+    }
+    for label in hostname.split('.') {
+        if label.is_empty() {
+            return Some("contains an empty label (leading, trailing or doubled `.`)".into());
+        }
+        if label.len() > MAX_LABEL_LEN {
+            return Some(format!("label '{label}' is longer than {MAX_LABEL_LEN} characters"));
+        }
+        if let Some(c) = label.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '-')) {
+            return Some(format!(
+                "'{c}' is not allowed in a hostname (letters, digits and `-` only, per RFC 1123)"
+            ));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Some(format!("label '{label}' must not start or end with `-`"));
+        }
+    }
+    None
+}
+
+// The portable POSIX account name rules as enforced by default by `useradd`/`adduser`:
+// lowercase letters, digits, `_` and `-`, starting with a letter or `_`.
+pub fn username_problem(username: &str) -> Option<String> {
+    let Some(first) = username.chars().next() else {
+        return Some("must not be empty".into());
+    };
+    if username.len() > MAX_USERNAME_LEN {
+        return Some(format!("is longer than {MAX_USERNAME_LEN} characters"));
+    }
+    if !(first.is_ascii_lowercase() || first == '_') {
+        return Some("must start with a lowercase letter or `_`".into());
+    }
+    if let Some(c) = username
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-')))
+    {
+        return Some(format!(
+            "'{c}' is not allowed in a username (lowercase letters, digits, `_` and `-` only)"
+        ));
+    }
+    None
+}
+
+// WSL compares distro names case-insensitively, so this is the key two profiles collide on.
+pub fn instance_key(hostname: &str) -> String {
+    hostname.to_ascii_lowercase()
+}
+
+pub fn validate_profile_names(profile: &ResolvedProfile) -> anyhow::Result<()> {
+    let mut problems = Vec::new();
+    if let Some(problem) = distro_name_problem(&profile.hostname).or_else(|| hostname_problem(&profile.hostname)) {
+        problems.push(format!("invalid hostname '{}': {}", profile.hostname, problem));
+    }
+    if let Some(problem) = username_problem(&profile.username) {
+        problems.push(format!("invalid username '{}': {}", profile.username, problem));
+    }
+    if !problems.is_empty() {
+        anyhow::bail!("{}", problems.join("; "));
+    }
+    Ok(())
+}
+
+pub fn validate_unique_names<'a>(profiles: impl IntoIterator<Item = &'a ResolvedProfile>) -> anyhow::Result<()> {
+    let mut seen: BTreeMap<String, &ResolvedProfile> = BTreeMap::new();
+    let mut collisions = Vec::new();
+    for profile in profiles {
+        match seen.get(&instance_key(&profile.hostname)) {
+            Some(other) => collisions.push(format!(
+                "profiles '{}' ({}) and '{}' ({})",
+                other.name, other.hostname, profile.name, profile.hostname
+            )),
+            None => {
+                seen.insert(instance_key(&profile.hostname), profile);
+            }
+        }
+    }
+    if !collisions.is_empty() {
+        anyhow::bail!(
+            "WSL instance names are case-insensitive, these would be the same instance: {}",
+            collisions.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+
+    fn profile(name: &str, hostname: &str) -> ResolvedProfile {
+        let profile: Profile = serde_yaml::from_str(&format!("hostname: {hostname}\ninstall_dir: /vms\n")).unwrap();
+        ResolvedProfile::resolve(name, &profile).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_hostnames() {
+        for hostname in [
+            "dev",
+            "UbuntuWslDev",
+            "web-01",
+            "a",
+            "dev.example.com",
+            "1box",
+            &"a".repeat(63),
+        ] {
+            assert_eq!(hostname_problem(hostname), None, "{hostname}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_hostnames() {
+        let long_label = "a".repeat(64);
+        let long_name = ["a".repeat(63), "b".repeat(63), "c".repeat(63), "d".repeat(63)].join(".");
+        for hostname in [
+            "",
+            "My_Box",
+            "has space",
+            "-dev",
+            "dev-",
+            "dev..local",
+            ".dev",
+            "dev.",
+            "dév",
+            long_label.as_str(),
+            long_name.as_str(),
+        ] {
+            assert!(hostname_problem(hostname).is_some(), "{hostname:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn distro_names_allow_underscores_but_not_flags_or_dots_only() {
+        for name in ["My_Box", "Ubuntu-24.04", "dev"] {
+            assert_eq!(distro_name_problem(name), None, "{name}");
+        }
+        for name in ["", "-d", ".", "..", "a b", "a/b", "a\\b", "a:b"] {
+            assert!(distro_name_problem(name).is_some(), "{name:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn accepts_valid_usernames() {
+        for username in ["wsluser", "_svc", "dev-user", "user_01", &"a".repeat(32)] {
+            assert_eq!(username_problem(username), None, "{username}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_usernames() {
+        let long = "a".repeat(33);
+        for username in [
+            "",
+            "Root",
+            "1user",
+            "-user",
+            "user name",
+            "user.name",
+            "usér",
+            long.as_str(),
+        ] {
+            assert!(username_problem(username).is_some(), "{username:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn instance_names_collide_regardless_of_case() {
+        let profiles = [
+            profile("one", "My_Box"),
+            profile("two", "my_box"),
+            profile("three", "other"),
+        ];
+        let err = validate_unique_names(&profiles).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "WSL instance names are case-insensitive, these would be the same instance: profiles 'one' (My_Box) \
+             and 'two' (my_box)"
+        );
+        assert!(validate_unique_names(&profiles[1..]).is_ok());
+    }
+
+    #[test]
+    fn profile_names_report_every_problem() {
+        let mut bad = profile("bad", "My_Box");
+        bad.username = "Root".into();
+        let err = validate_profile_names(&bad).unwrap_err().to_string();
+        assert!(
+            err.starts_with("invalid hostname 'My_Box': '_' is not allowed"),
+            "{err}"
+        );
+        assert!(
+            err.contains("; invalid username 'Root': must start with a lowercase letter"),
+            "{err}"
+        );
+        assert!(validate_profile_names(&profile("good", "dev")).is_ok());
+    }
+}
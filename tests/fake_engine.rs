@@ -0,0 +1,508 @@
+// Drives `WslManager` end to end through the fake engine, each test with its own fake WSL,
+// state file and install dir in a temporary directory.
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tempfile::TempDir;
+use wslforge::config::Profile;
+use wslforge::wsl::{
+    log_prefix, ApplyReport, CreateOutcome, EngineKind, EngineOptions, SnapshotKind, StateStore, WslManager,
+};
+
+struct Sandbox {
+    dir: TempDir,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        let sandbox = Self {
+            dir: TempDir::new().unwrap(),
+        };
+        sandbox.write_rootfs("rootfs.tar", "rootfs");
+        sandbox
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn manager(&self) -> WslManager {
+        self.manager_with(false)
+    }
+
+    fn manager_with(&self, dry_run: bool) -> WslManager {
+        let options = EngineOptions {
+            fake_state: Some(self.path("fake-wsl.json")),
+            ..EngineOptions::default()
+        };
+        WslManager::with_engine(EngineKind::Fake, &options, dry_run, false).with_state(self.state())
+    }
+
+    fn state(&self) -> StateStore {
+        StateStore::new(&self.path("state.json"))
+    }
+
+    fn write_rootfs(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.path(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    // A profile importing `rootfs.tar` into `vms/<hostname>`, with `extra` YAML on top.
+    fn profile(&self, hostname: &str, extra: &str) -> Profile {
+        let yaml = format!(
+            "hostname: {hostname}\ninstall_dir: {}\nimage: {{ type: file, path: {} }}\n{extra}",
+            self.path("vms").display(),
+            self.path("rootfs.tar").display()
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    // A profile whose rootfs does not exist, so creating it fails before anything is registered.
+    fn broken_profile(&self, hostname: &str) -> Profile {
+        let yaml = format!(
+            "hostname: {hostname}\ninstall_dir: {}\nimage: {{ type: file, path: {} }}\n",
+            self.path("vms").display(),
+            self.path("missing.tar").display()
+        );
+        serde_yaml::from_str(&yaml).unwrap()
+    }
+
+    fn instances(&self) -> Vec<String> {
+        let instances = self.manager().list_instances().unwrap();
+        instances.into_iter().map(|instance| instance.name).collect()
+    }
+}
+
+#[test]
+fn creates_a_missing_instance_and_records_it() {
+    let sandbox = Sandbox::new();
+    let profile = sandbox.profile("dev", "");
+
+    let outcome = sandbox.manager().create_instance("dev", &profile).unwrap();
+
+    assert_eq!(outcome, CreateOutcome::Created);
+    assert_eq!(sandbox.instances(), ["dev"]);
+    assert!(sandbox.path("vms/dev").is_dir());
+    let managed = sandbox.state().get("dev").unwrap().unwrap();
+    assert_eq!(managed.profile, "dev");
+    assert!(managed.fingerprint.starts_with("sha256:"));
+}
+
+#[test]
+fn skips_an_existing_instance_unless_override_is_set() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    manager.create_instance("dev", &sandbox.profile("dev", "")).unwrap();
+
+    let outcome = manager.create_instance("dev", &sandbox.profile("DEV", "")).unwrap();
+
+    assert_eq!(outcome, CreateOutcome::AlreadyExists);
+    assert_eq!(sandbox.instances(), ["dev"]);
+}
+
+#[test]
+fn replaces_an_existing_instance_with_override_always() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    manager.create_instance("dev", &sandbox.profile("dev", "")).unwrap();
+    let first = sandbox.state().get("dev").unwrap().unwrap();
+
+    let changed = sandbox.profile("dev", "override: always\nusername: other\n");
+    let outcome = manager.create_instance("dev", &changed).unwrap();
+
+    assert_eq!(outcome, CreateOutcome::Replaced);
+    assert_eq!(sandbox.instances(), ["dev"]);
+    let second = sandbox.state().get("dev").unwrap().unwrap();
+    assert_ne!(first.fingerprint, second.fingerprint);
+}
+
+#[test]
+fn if_changed_replaces_only_after_the_profile_changed() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "override: if_changed\n");
+    manager.create_instance("dev", &profile).unwrap();
+
+    assert_eq!(
+        manager.create_instance("dev", &profile).unwrap(),
+        CreateOutcome::AlreadyExists
+    );
+    let changed = sandbox.profile("dev", "override: if_changed\nusername: other\n");
+    assert_eq!(
+        manager.create_instance("dev", &changed).unwrap(),
+        CreateOutcome::Replaced
+    );
+}
+
+#[test]
+fn dry_run_changes_nothing() {
+    let sandbox = Sandbox::new();
+
+    let outcome = sandbox
+        .manager_with(true)
+        .create_instance("dev", &sandbox.profile("dev", ""))
+        .unwrap();
+
+    assert_eq!(outcome, CreateOutcome::Planned);
+    assert!(sandbox.instances().is_empty());
+    assert!(sandbox.state().get("dev").unwrap().is_none());
+}
+
+#[test]
+fn rolls_back_a_half_registered_instance_on_failure() {
+    let sandbox = Sandbox::new();
+    // The fake engine registers the instance, then fails on an empty archive.
+    sandbox.write_rootfs("rootfs.tar", "");
+    let profile = sandbox.profile("dev", "");
+
+    let err = sandbox.manager().create_instance("dev", &profile).unwrap_err();
+
+    assert!(format!("{err:#}").contains("empty or corrupt"), "{err:#}");
+    assert!(sandbox.instances().is_empty());
+    assert!(!sandbox.path("vms/dev").exists());
+    assert!(sandbox.state().get("dev").unwrap().is_none());
+}
+
+#[test]
+fn no_rollback_leaves_the_failed_instance_for_inspection() {
+    let sandbox = Sandbox::new();
+    sandbox.write_rootfs("rootfs.tar", "");
+    let profile = sandbox.profile("dev", "");
+
+    let manager = sandbox.manager().with_rollback(false);
+    manager.create_instance("dev", &profile).unwrap_err();
+
+    assert_eq!(sandbox.instances(), ["dev"]);
+    assert!(sandbox.path("vms/dev").is_dir());
+}
+
+fn outcomes(report: &ApplyReport) -> Vec<(&str, CreateOutcome)> {
+    report
+        .results
+        .iter()
+        .map(|result| (result.profile.as_str(), result.outcome))
+        .collect()
+}
+
+#[test]
+fn a_failing_profile_stops_the_ones_after_it() {
+    let sandbox = Sandbox::new();
+    let profiles = BTreeMap::from([
+        ("a".to_string(), sandbox.profile("a", "")),
+        ("b".to_string(), sandbox.broken_profile("b")),
+        ("c".to_string(), sandbox.profile("c", "")),
+    ]);
+
+    let report = sandbox.manager().apply_profiles(&profiles, 1, false);
+
+    assert_eq!(
+        outcomes(&report),
+        [("a", CreateOutcome::Created), ("b", CreateOutcome::Failed)]
+    );
+    assert!(report.results[1].error.is_some());
+    assert_eq!(sandbox.instances(), ["a"]);
+}
+
+#[test]
+fn keep_going_attempts_every_profile_and_reports_each_failure() {
+    let sandbox = Sandbox::new();
+    let profiles = BTreeMap::from([
+        ("a".to_string(), sandbox.broken_profile("a")),
+        ("b".to_string(), sandbox.profile("b", "")),
+        ("c".to_string(), sandbox.broken_profile("c")),
+    ]);
+
+    let report = sandbox.manager().apply_profiles(&profiles, 1, true);
+
+    assert_eq!(
+        outcomes(&report),
+        [
+            ("a", CreateOutcome::Failed),
+            ("b", CreateOutcome::Created),
+            ("c", CreateOutcome::Failed)
+        ]
+    );
+    let failed: Vec<bool> = report.results.iter().map(|result| result.error.is_some()).collect();
+    assert_eq!(failed, [true, false, true]);
+    assert_eq!(sandbox.instances(), ["b"]);
+}
+
+#[test]
+fn parallel_jobs_report_results_in_config_order() {
+    let sandbox = Sandbox::new();
+    let names = ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot"];
+    let profiles: BTreeMap<String, Profile> = names
+        .iter()
+        .map(|name| (name.to_string(), sandbox.profile(name, "")))
+        .collect();
+
+    let report = sandbox.manager().apply_profiles(&profiles, 3, false);
+
+    let reported: Vec<&str> = report.results.iter().map(|result| result.profile.as_str()).collect();
+    assert_eq!(reported, names);
+    assert_eq!(report.count(CreateOutcome::Created), names.len());
+}
+
+// Every log line with the profile prefix it was emitted under, as the CLI's logger sees it.
+// Tests run in parallel in this process, so each looks only for its own hostnames.
+static LOG_LINES: Mutex<Vec<(Option<String>, String)>> = Mutex::new(Vec::new());
+
+struct CapturingLogger;
+
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let line = (log_prefix(), record.args().to_string());
+        LOG_LINES.lock().unwrap_or_else(|e| e.into_inner()).push(line);
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn parallel_jobs_log_each_line_under_its_profile() {
+    if log::set_logger(&CapturingLogger).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+    let sandbox = Sandbox::new();
+    let names = ["prefixed-a", "prefixed-b", "prefixed-c"];
+    let profiles: BTreeMap<String, Profile> = names
+        .iter()
+        .map(|name| (name.to_string(), sandbox.profile(name, "")))
+        .collect();
+
+    sandbox.manager().apply_profiles(&profiles, 3, false);
+
+    let lines = LOG_LINES.lock().unwrap();
+    for name in names {
+        let created = format!("WSL instance '{name}' created successfully");
+        let prefixes: Vec<Option<&str>> = lines
+            .iter()
+            .filter(|(_, message)| message.contains(&created))
+            .map(|(prefix, _)| prefix.as_deref())
+            .collect();
+        assert_eq!(prefixes, [Some(name)]);
+    }
+}
+
+#[test]
+fn destroy_removes_the_instance_its_dir_and_its_state_entry() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "");
+    manager.create_instance("dev", &profile).unwrap();
+
+    manager.destroy_instance("dev", &profile, false).unwrap();
+
+    assert!(sandbox.instances().is_empty());
+    assert!(!sandbox.path("vms/dev").exists());
+    assert!(sandbox.state().get("dev").unwrap().is_none());
+}
+
+#[test]
+fn destroy_in_a_dry_run_keeps_everything() {
+    let sandbox = Sandbox::new();
+    let profile = sandbox.profile("dev", "");
+    sandbox.manager().create_instance("dev", &profile).unwrap();
+
+    let destroyed = sandbox
+        .manager_with(true)
+        .destroy_instance("dev", &profile, false)
+        .unwrap();
+
+    assert!(destroyed.unregistered);
+    assert_eq!(destroyed.removed, [sandbox.path("vms/dev")]);
+
+    assert_eq!(sandbox.instances(), ["dev"]);
+    assert!(sandbox.path("vms/dev").is_dir());
+    assert!(sandbox.state().get("dev").unwrap().is_some());
+}
+
+#[test]
+fn destroy_refuses_an_instance_wslforge_did_not_create() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "");
+    manager.create_instance("dev", &profile).unwrap();
+    sandbox.state().forget("dev").unwrap();
+
+    let err = manager.destroy_instance("dev", &profile, false).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "WSL instance 'dev' was not created by wslforge; pass --force to destroy it anyway"
+    );
+    assert_eq!(sandbox.instances(), ["dev"]);
+    assert!(sandbox.path("vms/dev").is_dir());
+}
+
+#[test]
+fn destroy_with_force_removes_an_instance_wslforge_did_not_create() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "");
+    manager.create_instance("dev", &profile).unwrap();
+    sandbox.state().forget("dev").unwrap();
+
+    manager.destroy_instance("dev", &profile, true).unwrap();
+
+    assert!(sandbox.instances().is_empty());
+    assert!(!sandbox.path("vms/dev").exists());
+}
+
+#[test]
+fn destroy_leaves_the_files_of_an_unrecorded_profile_alone_without_force() {
+    let sandbox = Sandbox::new();
+    let profile = sandbox.profile("data", "");
+    std::fs::create_dir_all(sandbox.path("vms/data")).unwrap();
+    std::fs::write(sandbox.path("vms/data/notes.txt"), "keep me").unwrap();
+
+    let err = sandbox.manager().destroy_instance("data", &profile, false).unwrap_err();
+
+    assert!(err.to_string().contains("pass --force to remove"), "{err}");
+    assert!(sandbox.path("vms/data/notes.txt").is_file());
+
+    sandbox.manager().destroy_instance("data", &profile, true).unwrap();
+    assert!(!sandbox.path("vms/data").exists());
+}
+
+#[test]
+fn a_state_file_that_cannot_be_written_does_not_roll_back_the_instance() {
+    let sandbox = Sandbox::new();
+    // A directory where the state file should be: reading and writing it both fail.
+    std::fs::create_dir(sandbox.path("state.json")).unwrap();
+    let profile = sandbox.profile("dev", "");
+
+    let outcome = sandbox.manager().create_instance("dev", &profile).unwrap();
+
+    assert_eq!(outcome, CreateOutcome::Created);
+    assert_eq!(sandbox.instances(), ["dev"]);
+    assert!(sandbox.path("vms/dev").is_dir());
+}
+
+#[test]
+fn backup_retention_prunes_old_backups_but_never_named_snapshots() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile(
+        "dev",
+        "override: always\nbackup_before_override: true\nbackup_retention: 2\n",
+    );
+    manager.create_instance("dev", &profile).unwrap();
+    manager.snapshot_instance("dev", &profile, Some("golden")).unwrap();
+
+    for _ in 0..4 {
+        assert_eq!(
+            manager.create_instance("dev", &profile).unwrap(),
+            CreateOutcome::Replaced
+        );
+    }
+
+    let snapshots = manager.list_snapshots("dev", &profile).unwrap();
+    let kinds: Vec<(&str, SnapshotKind)> = snapshots.iter().map(|s| (s.name.as_str(), s.kind)).collect();
+    assert_eq!(kinds.len(), 3, "{kinds:?}");
+    assert_eq!(kinds[0], ("golden", SnapshotKind::Snapshot));
+    assert!(kinds[1..].iter().all(|(_, kind)| *kind == SnapshotKind::Backup));
+    // Only the archives in the manifest are left on disk, next to the manifest itself.
+    let dir = sandbox.path("vms/dev.snapshots");
+    let mut files: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    let mut expected: Vec<String> = snapshots.iter().map(|s| s.archive.clone()).collect();
+    expected.push("manifest.json".into());
+    expected.sort();
+    assert_eq!(files, expected);
+}
+
+#[test]
+fn backups_record_the_fingerprint_of_the_instance_they_saved() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "override: always\nbackup_before_override: true\n");
+    manager.create_instance("dev", &profile).unwrap();
+    let before = sandbox.state().get("dev").unwrap().unwrap();
+
+    let changed = sandbox.profile(
+        "dev",
+        "override: always\nbackup_before_override: true\nusername: other\n",
+    );
+    manager.create_instance("dev", &changed).unwrap();
+
+    let backups = manager.list_snapshots("dev", &profile).unwrap();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(backups[0].fingerprint.as_deref(), Some(before.fingerprint.as_str()));
+}
+
+#[test]
+fn a_removed_snapshot_can_no_longer_be_restored() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "");
+    manager.create_instance("dev", &profile).unwrap();
+    manager
+        .snapshot_instance("dev", &profile, Some("before-upgrade"))
+        .unwrap();
+    let archive = sandbox.path("vms/dev.snapshots/before-upgrade.tar");
+    assert!(archive.is_file());
+
+    manager.remove_snapshot("dev", &profile, "before-upgrade").unwrap();
+
+    assert!(manager.list_snapshots("dev", &profile).unwrap().is_empty());
+    assert!(!archive.exists());
+    let err = manager
+        .restore_instance("dev", &profile, "before-upgrade", true)
+        .unwrap_err();
+    assert!(err.to_string().starts_with("no snapshot 'before-upgrade'"), "{err}");
+    assert_eq!(sandbox.instances(), ["dev"]);
+}
+
+#[test]
+fn restore_brings_back_a_listed_snapshot_with_its_fingerprint() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "");
+    manager.create_instance("dev", &profile).unwrap();
+    let taken = manager.snapshot_instance("dev", &profile, None).unwrap().unwrap();
+    let fingerprint = sandbox.state().get("dev").unwrap().unwrap().fingerprint;
+    assert_eq!(taken.fingerprint.as_deref(), Some(fingerprint.as_str()));
+
+    let err = manager
+        .restore_instance("dev", &profile, &taken.name, false)
+        .unwrap_err();
+    assert!(err.to_string().contains("pass --force"), "{err}");
+    manager.restore_instance("dev", &profile, &taken.name, true).unwrap();
+
+    assert_eq!(sandbox.instances(), ["dev"]);
+    assert_eq!(sandbox.state().get("dev").unwrap().unwrap().fingerprint, fingerprint);
+    // Restoring keeps the snapshot; only `snapshot rm` removes it.
+    let names: Vec<String> = manager
+        .list_snapshots("dev", &profile)
+        .unwrap()
+        .into_iter()
+        .map(|s| s.name)
+        .collect();
+    assert_eq!(names, [taken.name]);
+}
+
+#[test]
+fn snapshot_rm_and_restore_agree_when_an_archive_went_missing() {
+    let sandbox = Sandbox::new();
+    let manager = sandbox.manager();
+    let profile = sandbox.profile("dev", "");
+    manager.create_instance("dev", &profile).unwrap();
+    manager.snapshot_instance("dev", &profile, Some("lost")).unwrap();
+    std::fs::remove_file(sandbox.path("vms/dev.snapshots/lost.tar")).unwrap();
+
+    let err = manager.restore_instance("dev", &profile, "lost", true).unwrap_err();
+    assert!(err.to_string().starts_with("snapshot archive not found"), "{err}");
+    // The failed restore left the instance alone, and the entry can still be removed.
+    assert_eq!(sandbox.instances(), ["dev"]);
+    manager.remove_snapshot("dev", &profile, "lost").unwrap();
+    assert!(manager.list_snapshots("dev", &profile).unwrap().is_empty());
+}